@@ -0,0 +1,225 @@
+use std::{collections::HashSet, fs, path::Path, time::SystemTime};
+
+use colored::Colorize;
+use fli::Fli;
+
+use crate::{
+    commands::remove::remove_download,
+    helpers::{
+        api::format_age,
+        confirm,
+        file::{format_size, list_files_in_dir, sha256_file, ArchiveMetadata},
+        package::Version,
+        print_table,
+        registry::Registry,
+        store::get_store_dir,
+    },
+    providers::providers,
+};
+
+// a downloaded archive with what the cache commands need to know about it
+struct CachedArchive {
+    version: Version,
+    bytes: u64,
+    age: u64,
+}
+
+fn cached_archives() -> Vec<CachedArchive> {
+    let mut archives = Vec::new();
+    for package in providers() {
+        for version in package.get_local_versions() {
+            let Ok(metadata) = fs::metadata(version.get_location()) else {
+                continue;
+            };
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .map(|age| age.as_secs())
+                .unwrap_or(0);
+            archives.push(CachedArchive { version, bytes: metadata.len(), age });
+        }
+    }
+    archives.sort_by(|a, b| {
+        a.version
+            .get_name()
+            .cmp(b.version.get_name())
+            .then_with(|| b.version.get_semver().cmp(a.version.get_semver()))
+    });
+    archives
+}
+
+// sha256 of every archive an install (or one of its snapshots) was made from
+fn referenced_hashes() -> Result<HashSet<String>, String> {
+    let registry = Registry::load()?;
+    Ok(registry
+        .installations
+        .iter()
+        .chain(registry.snapshots.values())
+        .map(|installation| installation.archive_sha256.to_lowercase())
+        .collect())
+}
+
+// delete (or with --dry-run only list) archives, returns the bytes freed
+fn remove_archives(archives: &[&CachedArchive], dry_run: bool) -> u64 {
+    let mut freed = 0;
+    for archive in archives {
+        let version = &archive.version;
+        if dry_run {
+            println!("Would remove {} {} ({})", version.get_name(), version.get_version(), version.get_size());
+            freed += archive.bytes;
+            continue;
+        }
+        match remove_download(version) {
+            Ok(()) => {
+                println!("Removed {} {} ({})", version.get_name(), version.get_version(), version.get_size());
+                freed += archive.bytes;
+            }
+            Err(e) => println!("❌ {}", e.red()),
+        }
+    }
+    freed
+}
+
+fn print_freed(freed: u64, dry_run: bool) {
+    match dry_run {
+        true => println!("\n{} {}", format_size(freed).bold(), "would be freed".dimmed()),
+        false => println!("\n✅ Freed {}", format_size(freed).bold()),
+    }
+}
+
+pub fn cache_list(_x: &Fli) {
+    let archives = cached_archives();
+    if archives.is_empty() {
+        println!("No downloads cached");
+        return;
+    }
+    let headers = ["Package".to_string(), "Version".to_string(), "Size".to_string(), "Age".to_string(), "Location".to_string()];
+    let table_data: Vec<[String; 5]> = archives
+        .iter()
+        .map(|archive| {
+            [
+                archive.version.get_name().to_string(),
+                archive.version.get_version().to_string(),
+                archive.version.get_size().to_string(),
+                format_age(archive.age),
+                archive.version.get_location().to_string(),
+            ]
+        })
+        .collect();
+    print_table(headers, &table_data);
+}
+
+pub fn cache_size(_x: &Fli) {
+    let archives = cached_archives();
+    let headers = ["Package".to_string(), "Downloads".to_string(), "Size".to_string()];
+    let mut table_data: Vec<[String; 3]> = Vec::new();
+    for package in providers() {
+        let package_archives: Vec<&CachedArchive> = archives
+            .iter()
+            .filter(|archive| archive.version.get_name() == package.display_name())
+            .collect();
+        let bytes: u64 = package_archives.iter().map(|archive| archive.bytes).sum();
+        table_data.push([package.display_name().to_string(), package_archives.len().to_string(), format_size(bytes)]);
+    }
+    let total: u64 = archives.iter().map(|archive| archive.bytes).sum();
+    table_data.push(["Total".to_string(), archives.len().to_string(), format_size(total)]);
+    print_table(headers, &table_data);
+}
+
+// delete every download, or with --older-than only those older than that many days
+pub fn cache_clean(x: &Fli) {
+    let dry_run = x.is_passed("dry-run".to_owned());
+    let max_age = match x.get_values("older-than".to_owned()) {
+        Ok(days) => match days.first().unwrap().parse::<u64>() {
+            Ok(days) => Some(days * 24 * 60 * 60),
+            Err(_) => {
+                x.print_help("Please provide the number of days as a whole number");
+                return;
+            }
+        },
+        Err(_) => None,
+    };
+    let archives = cached_archives();
+    let to_remove: Vec<&CachedArchive> = archives
+        .iter()
+        .filter(|archive| max_age.is_none_or(|max_age| archive.age > max_age))
+        .collect();
+    if to_remove.is_empty() {
+        println!("Nothing to clean");
+        return;
+    }
+    if !dry_run && !confirm(&format!("Remove {} download(s)?", to_remove.len()), x.is_passed("yes".to_owned())) {
+        println!("Nothing removed");
+        return;
+    }
+    print_freed(remove_archives(&to_remove, dry_run), dry_run);
+}
+
+// the sha256 of a download, from its sidecar when it recorded one so the archive isn't hashed again
+fn archive_sha256(archive: &CachedArchive) -> std::io::Result<String> {
+    let location = Path::new(archive.version.get_location());
+    match ArchiveMetadata::read(location) {
+        Some(metadata) if !metadata.sha256.is_empty() => Ok(metadata.sha256.to_lowercase()),
+        _ => sha256_file(location),
+    }
+}
+
+// delete downloads and store entries no install or snapshot was made from
+pub fn cache_prune(x: &Fli) {
+    let dry_run = x.is_passed("dry-run".to_owned());
+    let referenced = match referenced_hashes() {
+        Ok(referenced) => referenced,
+        Err(e) => {
+            println!("❌ {}", e.red());
+            return;
+        }
+    };
+    let archives = cached_archives();
+    // hashes of downloads that are kept or already being removed, their store copies are handled with them
+    let mut seen = referenced.clone();
+    let mut to_remove = Vec::new();
+    for archive in &archives {
+        match archive_sha256(archive) {
+            Ok(sha256) => {
+                if !referenced.contains(&sha256) {
+                    to_remove.push(archive);
+                }
+                seen.insert(sha256);
+            }
+            Err(e) => println!("⚠️  {} {}", archive.version.get_location().yellow(), e.to_string().dimmed()),
+        }
+    }
+    // store copies whose download is gone
+    let unused_stored: Vec<_> = list_files_in_dir(&get_store_dir())
+        .into_iter()
+        .filter(|stored| !seen.contains(&stored.file_name().unwrap_or_default().to_string_lossy().to_string()))
+        .collect();
+    if to_remove.is_empty() && unused_stored.is_empty() {
+        println!("Nothing to prune");
+        return;
+    }
+    if !dry_run
+        && !confirm(
+            &format!("Remove {} download(s) and {} store entry(ies)?", to_remove.len(), unused_stored.len()),
+            x.is_passed("yes".to_owned()),
+        )
+    {
+        println!("Nothing removed");
+        return;
+    }
+
+    let mut freed = remove_archives(&to_remove, dry_run);
+    for stored in unused_stored {
+        let name = stored.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let bytes = stored.metadata().map(|m| m.len()).unwrap_or(0);
+        if dry_run {
+            println!("Would remove unused store entry {}", name.dimmed());
+        } else if let Err(e) = fs::remove_file(&stored) {
+            println!("❌ {}", e.to_string().red());
+            continue;
+        }
+        freed += bytes;
+    }
+    print_freed(freed, dry_run);
+}
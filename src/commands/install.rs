@@ -0,0 +1,127 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use colored::*;
+
+use crate::{
+    commands::{get_extract_options, get_manifest_options, resolve_local_version},
+    helpers::{
+        api::ManifestOptions,
+        archive::ExtractOptions,
+        package::{AppInstallError, Package},
+    },
+    providers::{providers, PackageProvider},
+};
+use fli::Fli;
+
+
+pub fn install_app(x: &Fli) {
+    let Some(provider) = providers().iter().find(|provider| x.is_passed(provider.name().to_owned())) else {
+        x.print_help("Please provide the tool to install");
+        return;
+    };
+    let provider = provider.as_ref();
+    let name = provider.display_name();
+    // packages with an install root of their own don't need a path
+    let target_path = match (x.get_values("path".to_owned()), provider.install_root()) {
+        (Ok(path), _) => Some(path.first().unwrap().to_string()),
+        (Err(_), Some(_)) => None,
+        (Err(_), None) => {
+            x.print_help("Please provide a path using -pa or --path");
+            return;
+        }
+    };
+    let version = match x.get_values(provider.name().to_owned()) {
+        Ok(versions) => versions.first().unwrap().to_string(),
+        Err(_) => {
+            x.print_help(&format!("Please provide a {} version", name));
+            return;
+        }
+    };
+    println!(
+        "Attempting to install {} version {} to {}",
+        name,
+        version.bold().blue(),
+        target_path
+            .clone()
+            .or_else(|| provider.install_root().map(|root| root.display().to_string()))
+            .unwrap_or_default()
+            .bold()
+            .blue()
+    );
+    let options = match get_extract_options(x) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("❌ {}", e.red());
+            return;
+        }
+    };
+    match install_package_version(provider, &version, target_path.as_deref(), &options, &get_manifest_options(x)) {
+        Ok((version, target_path, backup)) => {
+            println!("✅ {} version {} installed successfully in {}", name, version, target_path.display());
+            print_detected(provider, &target_path);
+            print_backup(backup);
+        }
+        Err(e) => {
+            //color in grey or gray or light black
+            println!("❌ {}: {}", format!("Failed to install {} version", name).red(), format!("{}", e).dimmed());
+        }
+    }
+}
+
+// where the installed binary is and which version it reports, when the provider can tell
+pub fn print_detected(provider: &dyn PackageProvider, path: &Path) {
+    let Some(binary) = provider.locate_binary(path) else {
+        return;
+    };
+    let version = provider.detect_version(path).unwrap_or_else(|| "unknown version".to_string());
+    println!("{}", format!("{} ({})", binary.display(), version).dimmed());
+}
+
+pub fn print_backup(backup: Option<PathBuf>) {
+    if let Some(backup) = backup {
+        println!("{} {}", "Previous files were kept in".dimmed(), backup.display().to_string().dimmed());
+    }
+}
+
+// install the downloaded version a version or constraint picks, into `target_path` or else `<install root>/<version>`.
+// returns that version, where it went and where the previous files went
+pub fn install_package_version(
+    provider: &'static dyn PackageProvider,
+    version: &str,
+    target_path: Option<&str>,
+    options: &ExtractOptions,
+    manifest_options: &ManifestOptions,
+) -> Result<(String, PathBuf, Option<PathBuf>), AppInstallError> {
+    let mut app = Package::new(provider);
+    app.load_local_versions();
+    let version = resolve_local_version(&app, version, manifest_options)?;
+    // an explicit path has to exist already, install roots are xupg's own and made when needed
+    let (target_path, created) = match (target_path, provider.install_root()) {
+        (Some(path), _) => (PathBuf::from(path), false),
+        (None, Some(root)) => {
+            let path = root.join(&version);
+            let created = !path.exists();
+            if created {
+                fs::create_dir_all(&path)
+                    .map_err(|e| AppInstallError::InstallFailed(format!("Failed to create {}: {}", path.display(), e)))?;
+            }
+            (path, created)
+        }
+        (None, None) => return Err(AppInstallError::InstallFailed("No install path given".to_string())),
+    };
+    let backup = app.install_version(&version, &target_path.to_string_lossy(), options, true)?;
+    // the empty directory made above isn't worth keeping as a snapshot
+    let backup = match backup {
+        Some(backup) if created => {
+            let _ = fs::remove_dir(&backup);
+            None
+        }
+        backup => backup,
+    };
+    Ok((version, target_path, backup))
+}
+
+
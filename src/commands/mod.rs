@@ -1,17 +1,23 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use colored::Colorize;
 use fli::Fli;
 
-use crate::helpers::{api::{fetch_releases, ReleaseInfo}, file::{download_multiple_files, get_download_path, DownloadInfo}, get_platform_os, package::SupportedPackages, print_table};
+use crate::{helpers::{api::{format_age, now_secs, ManifestOptions}, archive::{archive_extension, ExtractOptions}, file::{download_multiple_files, get_download_path, parse_rate, ArchiveMetadata, DownloadInfo, DownloadOptions}, get_platform_os, package::{AppInstallError, Package}, print_table, registry::Registry, version::{same_version, SemVer}}, providers::{providers, PackageProvider}};
 
 
-pub mod php;
+pub mod install;
 pub mod xampp;
 pub mod phpmyadmin;
+pub mod verify;
+pub mod cache;
+pub mod rollback;
+pub mod remove;
+pub mod uninstall;
+pub mod upgrade;
 
 
-pub fn get_app_list(package : &SupportedPackages, online: bool) -> Result<([String; 2], Vec<[String; 2]>), String> {
+pub fn get_app_list(package : &dyn PackageProvider, online: bool, manifest_options: &ManifestOptions) -> Result<([String; 2], Vec<[String; 2]>), String> {
     let platform = get_platform_os();
 
     // If platform is not supported, print error and return early
@@ -24,34 +30,11 @@ pub fn get_app_list(package : &SupportedPackages, online: bool) -> Result<([Stri
     // Use `match` block and handle errors directly inside the block
     let table_data: Vec<[String; 2]> = match online {
         true => {
-            let data = fetch_releases();
-            if data.is_err() {
-                return Err("Failed to fetch data".to_string());
-            }
-            let data = data.unwrap();
-
-            let platform_tools = data.platforms.get(&platform);
-            if platform_tools.is_none() {
-                return Err("Platform not supported".to_string());
-            }
-            let platform_tools = platform_tools.unwrap();
-            let package_name = package.get_name();
-            let package_name = package_name.to_lowercase();
-            let apppackage = platform_tools.tools.get(&package_name);
-            if !apppackage.is_some() {
-                return Err(format!("{} not available for this platform", package_name.to_uppercase()));
-            }
-
-            let apppackage = apppackage.unwrap();
+            let apppackage = package.list_remote_versions(&platform, manifest_options)?;
             let mut table_data: Vec<[String; 2]> = Vec::new();
 
-            // Sort versions in descending order and collect them into table_data
-            let mut versions_with_info: Vec<(&String, &ReleaseInfo)> =
-                apppackage.versions.iter().collect();
-            versions_with_info.sort_by(|(version_a, _), (version_b, _)| {
-                version_b.cmp(version_a) // Sort in descending order
-            });
-            for (version, info) in versions_with_info {
+            // newest first, compared as versions so 8.10 comes before 8.9
+            for (version, info) in apppackage.sorted() {
                 table_data.push([version.to_string(), info.release_date.to_string()]);
             }
 
@@ -59,7 +42,9 @@ pub fn get_app_list(package : &SupportedPackages, online: bool) -> Result<([Stri
         }
         false => {
             headers[1] = "Location".to_string();
-            let local_versions = package.get_local_versions();
+            let (mut local_versions, unrecognized) = package.scan_local_versions();
+            report_unrecognized(&unrecognized);
+            local_versions.sort_by(|a, b| b.get_semver().cmp(a.get_semver()));
             let table_data: Vec<[String; 2]> = local_versions
                 .iter()
                 .map(|version| {
@@ -69,70 +54,197 @@ pub fn get_app_list(package : &SupportedPackages, online: bool) -> Result<([Stri
             table_data
         }
     };
-    return Ok((headers, table_data));
+    Ok((headers, table_data))
 }
 
 
-pub fn get_app(package : &SupportedPackages,  versions : Vec<String>) -> Result<bool, String> {
-    let platform = get_platform_os();
-    if platform.is_none() {
-        return Err("Platform not supported".to_string());
+// files in a download directory that no sidecar or archive name says anything about, they are left alone
+pub fn report_unrecognized(files: &[PathBuf]) {
+    for file in files {
+        println!("⚠️  {} {}", "Skipping unrecognized file".yellow(), file.display().to_string().dimmed());
     }
-    let platform = platform.unwrap();
-    let app_data = fetch_releases();
-    if app_data.is_err() {
-        return Err("Failed to fetch data".to_string());
+}
+
+// the downloaded version a version or constraint picks. the cached manifest, when it loads,
+// tells which versions are LTS and whether a newer match could be downloaded
+pub fn resolve_local_version(app: &Package, requirement: &str, manifest_options: &ManifestOptions) -> Result<String, AppInstallError> {
+    let platform = get_platform_os().unwrap_or_default();
+    let parsed = app
+        .provider
+        .parse_requirement(requirement, &platform, manifest_options)
+        .map_err(|_| AppInstallError::VersionNotAvailable(requirement.to_string()))?;
+    let package_name = app.provider.name();
+    let remote = app.provider.list_remote_versions(&platform, manifest_options).ok();
+    let tool = remote.as_ref();
+    let lts: Vec<SemVer> = tool
+        .map(|tool| {
+            tool.versions
+                .iter()
+                .filter(|(_, release)| release.lts)
+                .filter_map(|(key, _)| key.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let local = app.resolve_version(&parsed, &lts);
+    if let Some((key, release)) = tool.and_then(|tool| tool.resolve(&parsed)) {
+        // compared as the exact releases, "8.1" may have moved on to a newer build
+        let online = release.version.as_deref().unwrap_or(key);
+        let newer = match (online.parse::<SemVer>(), local.map(|local| local.get_release_version().parse::<SemVer>())) {
+            (Ok(online), Some(Ok(local))) => online > local,
+            (_, local) => local.is_none(),
+        };
+        if newer {
+            println!(
+                "💡 {} {}",
+                format!("{} {} is available online, get it with", app.get_name(), online).yellow(),
+                format!("xupg get --{} {}", package_name, online).bold()
+            );
+        }
     }
+    let local = local.ok_or_else(|| AppInstallError::VersionNotAvailable(requirement.to_string()))?;
+    let resolved = local.get_release_version();
+    if !same_version(&resolved, requirement) {
+        println!("🔎 {} {} {}", requirement.bold(), "resolved to".dimmed(), format!("{} {}", app.get_name(), resolved).bold().blue());
+    }
+    Ok(local.get_version().to_string())
+}
 
-    let app_data = app_data.unwrap();
-    let platform_tools = app_data.platforms.get(&platform);
-    if platform_tools.is_none() {
+pub fn get_app(package : &dyn PackageProvider,  versions : Vec<String>, manifest_options: &ManifestOptions, download_options: &DownloadOptions) -> Result<bool, String> {
+    let platform = get_platform_os();
+    if platform.is_none() {
         return Err("Platform not supported".to_string());
     }
-    let platform_tools = platform_tools.unwrap();
-    let package_name = package.get_name();
-    let package_name = package_name.to_lowercase();
-    let apppackage = platform_tools.tools.get(&package_name);
-    if !apppackage.is_some() {
-        return Err(format!("{} not available for this platform", package_name.to_uppercase().bold().blue()));
-    }
-    let apppackage = apppackage.unwrap();
+    let platform = platform.unwrap();
+    let package_name = package.name();
     let mut to_download = Vec::new();
-    for version in versions {
-        let version_info = apppackage.versions.get(&version);
-        if version_info.is_none() {
-            return Err(format!("{} {}", "Version not available".red(), version));
+    for requirement in versions {
+        let artifact = package.resolve_artifact(&requirement, &platform, manifest_options)?;
+        // tell the user when a constraint like "^8.1" picked a version they didn't type, as the exact release it is
+        let resolved = artifact.release_version.as_deref().unwrap_or(&artifact.version);
+        if !same_version(resolved, &requirement) {
+            println!("🔎 {} {} {}", requirement.bold(), "resolved to".dimmed(), format!("{} {}", package.display_name(), resolved).bold().blue());
         }
-        let version_info = version_info.unwrap();
-        let download_url = version_info.url.clone();
-        let extension = Path::new(&download_url)
-            .extension()
-            .unwrap()
-            .to_str()
-            .unwrap();
-        let target_path = get_download_path(&package_name, format!("{}-{}.{}", package_name, version, extension).as_str());
-        if target_path.exists() {
-            return Err(format!("{} {}", "File already exists".red(), target_path.display()));
-        }
-        to_download.push(DownloadInfo::new(download_url.clone(), target_path));
+        let version = &artifact.version;
+        let download_url = artifact.url.clone();
+        // keep "tar.gz" style extensions whole so the archive type can be told from the name
+        let extension = artifact
+            .extension
+            .as_deref()
+            .or_else(|| archive_extension(&download_url))
+            .or_else(|| Path::new(&download_url).extension()?.to_str())
+            .ok_or_else(|| format!("Can't tell the archive type of {}", download_url))?;
+        let target_path = get_download_path(package_name, format!("{}-{}.{}", package_name, version, extension).as_str());
+        to_download.push(
+            DownloadInfo::new(download_url.clone(), target_path)
+                .with_integrity(artifact.sha256.clone(), artifact.size)
+                .with_metadata(
+                    ArchiveMetadata::new(package_name, version, &download_url, &platform)
+                        .with_release_version(artifact.release_version.clone()),
+                ),
+        );
     }
     if to_download.is_empty() {
         return Err("No files to download".to_string());
     }
-    if let Err(e) = download_multiple_files(to_download) {
+    if let Err(e) = download_multiple_files(to_download, download_options) {
         return Err(format!("{} {}", "Failed to download files".red(), e));
     }
-    return Ok(true);
+    Ok(true)
 }
 
 
+// manifest options shared by every command that reads the release manifest
+pub fn get_manifest_options(x: &Fli) -> ManifestOptions {
+    ManifestOptions {
+        refresh: x.is_passed("refresh".to_owned()),
+        insecure: x.is_passed("insecure".to_owned()),
+    }
+}
+
+// download settings from the config, overridden by --jobs and --limit-rate
+pub fn get_download_options(x: &Fli) -> Result<DownloadOptions, String> {
+    let mut options = DownloadOptions::from_config();
+    if let Ok(jobs) = x.get_values("jobs".to_owned()) {
+        let jobs = jobs.first().unwrap();
+        let jobs = jobs
+            .parse::<usize>()
+            .ok()
+            .filter(|jobs| *jobs > 0)
+            .ok_or_else(|| format!("Invalid number of jobs {}", jobs))?;
+        options = options.with_jobs(jobs);
+    }
+    if let Ok(rate) = x.get_values("limit-rate".to_owned()) {
+        options = options.with_rate_limit(Some(parse_rate(rate.first().unwrap())?));
+    }
+    Ok(options)
+}
+
+// extraction settings from the config and the --strip-components flag
+pub fn get_extract_options(x: &Fli) -> Result<ExtractOptions, String> {
+    let options = ExtractOptions::from_config();
+    match x.get_values("strip-components".to_owned()) {
+        Ok(strip) => {
+            let strip = strip.first().unwrap();
+            let strip = strip
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid number of components to strip {}", strip))?;
+            Ok(options.with_strip_components(Some(strip)))
+        }
+        Err(_) => Ok(options),
+    }
+}
+
+// installs recorded in the registry, only those of the packages asked for when any are
+fn list_installed(x: &Fli) {
+    let registry = match Registry::load() {
+        Ok(registry) => registry,
+        Err(e) => {
+            println!("❌ {}", e.red());
+            return;
+        }
+    };
+    let packages: Vec<String> = providers()
+        .iter()
+        .map(|provider| provider.name().to_string())
+        .filter(|name| x.is_passed(name.to_owned()))
+        .collect();
+    let now = now_secs();
+    let mut installations: Vec<_> = registry.installations.iter().collect();
+    installations.sort_by(|a, b| a.package.cmp(&b.package));
+    let table_data: Vec<[String; 4]> = installations
+        .into_iter()
+        .filter(|installation| packages.is_empty() || packages.contains(&installation.package))
+        .map(|installation| {
+            [
+                installation.package.to_uppercase(),
+                installation.version.clone(),
+                installation.path.clone(),
+                format!("{} ago", format_age(now.saturating_sub(installation.installed_at))),
+            ]
+        })
+        .collect();
+    if table_data.is_empty() {
+        println!("Nothing installed yet");
+        return;
+    }
+    let headers = ["Package".to_string(), "Version".to_string(), "Path".to_string(), "Installed".to_string()];
+    println!("\n{} \n", "Installed versions:".red());
+    print_table(headers, &table_data);
+}
+
 pub fn list_app(x: &Fli) {
-    for package in SupportedPackages::iter() {
-        if x.is_passed(package.get_name().to_lowercase()) {
-            let result = get_app_list(&package, x.is_passed("online".to_owned()));
+    if x.is_passed("installed".to_owned()) {
+        list_installed(x);
+        return;
+    }
+    let manifest_options = get_manifest_options(x);
+    for package in providers() {
+        if x.is_passed(package.name().to_owned()) {
+            let result = get_app_list(package.as_ref(), x.is_passed("online".to_owned()), &manifest_options);
             match result {
                 Ok((headers, table_data)) => {
-                    println!("\n{} {}: \n", format!("Available {} versions for", package.get_name()).red(), get_platform_os().unwrap().to_uppercase().bold().blue());
+                    println!("\n{} {}: \n", format!("Available {} versions for", package.display_name()).red(), get_platform_os().unwrap().to_uppercase().bold().blue());
                     print_table(headers, &table_data);
                 }
                 Err(e) => {
@@ -144,11 +256,19 @@ pub fn list_app(x: &Fli) {
 }
 
 pub fn download_app(x: &Fli) {
-    for package in SupportedPackages::iter() {
-        if !x.is_passed(package.get_name().to_lowercase()) {
+    let manifest_options = get_manifest_options(x);
+    let download_options = match get_download_options(x) {
+        Ok(options) => options,
+        Err(e) => {
+            x.print_help(&e);
+            return;
+        }
+    };
+    for package in providers() {
+        if !x.is_passed(package.name().to_owned()) {
             continue;
         }
-        let versions = x.get_values(package.get_name().to_lowercase());
+        let versions = x.get_values(package.name().to_owned());
         if versions.is_err() {
             x.print_help("Please provide a version to install");
             return;
@@ -158,9 +278,9 @@ pub fn download_app(x: &Fli) {
             x.print_help("Please provide a version to install");
             return;
         }
-        if let Err(e) = get_app(&package, versions) {
+        if let Err(e) = get_app(package.as_ref(), versions, &manifest_options, &download_options) {
             x.print_help(&e);
         }
-        println!("✅ Installed {} versions successfully", package.get_name().to_uppercase().bold().blue());
+        println!("✅ Installed {} versions successfully", package.display_name().to_uppercase().bold().blue());
     }
 }
\ No newline at end of file
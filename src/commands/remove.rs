@@ -0,0 +1,67 @@
+use std::{fs, path::Path};
+
+use colored::Colorize;
+use fli::Fli;
+
+use crate::{
+    helpers::{
+        confirm,
+        file::{get_metadata_path, sha256_file},
+        package::Version,
+        store::get_store_path,
+        version::same_version,
+    },
+    providers::providers,
+};
+
+// delete a downloaded archive, its metadata sidecar and its copy in the store
+pub fn remove_download(version: &Version) -> Result<(), String> {
+    let file = Path::new(version.get_location());
+    if let Ok(sha256) = sha256_file(file) {
+        let stored = get_store_path(&sha256);
+        if stored.exists() {
+            fs::remove_file(&stored).map_err(|e| format!("Failed to remove {}: {}", stored.display(), e))?;
+        }
+    }
+    fs::remove_file(file).map_err(|e| format!("Failed to remove {}: {}", file.display(), e))?;
+    let metadata = get_metadata_path(file);
+    if metadata.exists() {
+        fs::remove_file(&metadata).map_err(|e| format!("Failed to remove {}: {}", metadata.display(), e))?;
+    }
+    Ok(())
+}
+
+// delete downloaded archives, e.g `xupg remove -p 8.1 8.2`
+pub fn remove_downloads(x: &Fli) {
+    let mut to_remove = Vec::new();
+    for package in providers() {
+        let Ok(versions) = x.get_values(package.name().to_owned()) else {
+            continue;
+        };
+        let local_versions = package.get_local_versions();
+        for version in versions {
+            match local_versions.iter().position(|local| same_version(local.get_version(), &version)) {
+                Some(i) => to_remove.push(local_versions[i].clone()),
+                None => println!("⚠️  {} {} {}", package.display_name(), version.bold(), "is not downloaded".yellow()),
+            }
+        }
+    }
+    if to_remove.is_empty() {
+        x.print_help("Please provide the versions to remove");
+        return;
+    }
+
+    for version in &to_remove {
+        println!("  {} {} ({})", version.get_name(), version.get_version().bold(), version.get_location().dimmed());
+    }
+    if !confirm(&format!("Remove {} download(s)?", to_remove.len()), x.is_passed("yes".to_owned())) {
+        println!("Nothing removed");
+        return;
+    }
+    for version in &to_remove {
+        match remove_download(version) {
+            Ok(()) => println!("✅ Removed {} {}", version.get_name(), version.get_version()),
+            Err(e) => println!("❌ {}", e.red()),
+        }
+    }
+}
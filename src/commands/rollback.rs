@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use colored::Colorize;
+use fli::Fli;
+
+use crate::{
+    commands::install::print_backup,
+    helpers::{
+        api::{format_age, now_secs},
+        install::{list_snapshots, restore_snapshot},
+        print_table,
+        registry::Registry,
+    },
+};
+
+// list the snapshots of an install path, or restore one of them with --to
+pub fn rollback_install(x: &Fli) {
+    let target_path = match x.get_values("path".to_owned()) {
+        Ok(path) => path.first().unwrap().to_string(),
+        Err(_) => {
+            x.print_help("Please provide the install path using -pa or --path");
+            return;
+        }
+    };
+    let target = Path::new(&target_path);
+    let snapshots = match list_snapshots(target) {
+        Ok(snapshots) => snapshots,
+        Err(e) => {
+            println!("❌ {}", e.red());
+            return;
+        }
+    };
+    if snapshots.is_empty() {
+        println!("No snapshots of {}", target_path.bold().blue());
+        return;
+    }
+
+    let Ok(choice) = x.get_values("to".to_owned()) else {
+        let now = now_secs();
+        let headers = ["#".to_string(), "Taken".to_string(), "Location".to_string()];
+        let table_data: Vec<[String; 3]> = snapshots
+            .iter()
+            .enumerate()
+            .map(|(i, snapshot)| {
+                [
+                    (i + 1).to_string(),
+                    format!("{} ago", format_age(now.saturating_sub(snapshot.taken_at))),
+                    snapshot.path.display().to_string(),
+                ]
+            })
+            .collect();
+        println!("\n{} {}: \n", "Snapshots of".red(), target_path.bold().blue());
+        print_table(headers, &table_data);
+        println!("\nRestore one with {}", format!("xupg rollback -pa {} -t <#>", target_path).bold());
+        return;
+    };
+
+    let choice = choice.first().unwrap();
+    let snapshot = match choice.parse::<usize>().ok().and_then(|i| i.checked_sub(1)).and_then(|i| snapshots.get(i)) {
+        Some(snapshot) => snapshot,
+        None => {
+            println!("❌ {} {}", "No snapshot numbered".red(), choice);
+            return;
+        }
+    };
+    match restore_snapshot(target, &snapshot.path) {
+        Ok(backup) => {
+            if let Err(e) = Registry::update(|registry| registry.record_rollback(target, &snapshot.path, backup.as_deref())) {
+                eprintln!("⚠️  {} {}", "Rolled back, but failed to record it:".yellow(), e.dimmed());
+            }
+            println!("✅ Restored {} from {}", target_path, snapshot.path.display());
+            print_backup(backup);
+        }
+        Err(e) => println!("❌ {}: {}", "Failed to roll back".red(), e.dimmed()),
+    }
+}
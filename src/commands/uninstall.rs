@@ -0,0 +1,89 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use colored::Colorize;
+use fli::Fli;
+
+use crate::helpers::{
+    confirm,
+    registry::{Installation, Registry},
+};
+
+// remove the files an install placed, then the directories that are left empty.
+// returns how many files were removed
+fn remove_installed_files(installation: &Installation) -> Result<usize, String> {
+    let root = PathBuf::from(&installation.path);
+    let mut removed = 0;
+    let mut dirs = Vec::new();
+    for file in &installation.files {
+        let relative = Path::new(file);
+        // never follow a manifest entry out of the install path
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            continue;
+        }
+        let path = root.join(relative);
+        if path.symlink_metadata().is_err() {
+            continue;
+        }
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        removed += 1;
+        dirs.extend(path.ancestors().skip(1).take_while(|dir| *dir != root).map(Path::to_path_buf));
+    }
+    // deepest first, directories with files xupg didn't place stay
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    dirs.dedup();
+    for dir in dirs {
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(removed)
+}
+
+// remove an install from its path using the file manifest in the registry
+pub fn uninstall_app(x: &Fli) {
+    let target_path = match x.get_values("path".to_owned()) {
+        Ok(path) => path.first().unwrap().to_string(),
+        Err(_) => {
+            x.print_help("Please provide the install path using -pa or --path");
+            return;
+        }
+    };
+    let installation = match Registry::load() {
+        Ok(registry) => registry.find(Path::new(&target_path)).cloned(),
+        Err(e) => {
+            println!("❌ {}", e.red());
+            return;
+        }
+    };
+    let Some(installation) = installation else {
+        println!("❌ {} {}", "Nothing was installed by xupg at".red(), target_path);
+        return;
+    };
+
+    let question = format!(
+        "Remove the {} files of {} {} from {}?",
+        installation.files.len(),
+        installation.package.to_uppercase(),
+        installation.version,
+        installation.path
+    );
+    if !confirm(&question, x.is_passed("yes".to_owned())) {
+        println!("Nothing removed");
+        return;
+    }
+    match remove_installed_files(&installation) {
+        Ok(removed) => {
+            if let Err(e) = Registry::update(|registry| registry.remove(Path::new(&installation.path))) {
+                println!("⚠️  {} {}", "Uninstalled, but failed to update the registry:".yellow(), e.dimmed());
+            }
+            println!(
+                "✅ Uninstalled {} {} ({} files removed)",
+                installation.package.to_uppercase(),
+                installation.version,
+                removed
+            );
+        }
+        Err(e) => println!("❌ {}: {}", "Failed to uninstall".red(), e.dimmed()),
+    }
+}
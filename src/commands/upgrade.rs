@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use colored::Colorize;
+use fli::Fli;
+
+use crate::{
+    commands::{get_app, get_manifest_options, install::print_backup},
+    helpers::{
+        archive::ExtractOptions,
+        file::DownloadOptions,
+        get_platform_os,
+        package::Package,
+        registry::{Installation, Registry},
+        version::same_version,
+    },
+    providers::find_provider,
+};
+
+// whether a published value differs from the one recorded at install time, None when either isn't known
+fn differs(published: Option<&str>, installed: Option<&str>, same: impl Fn(&str, &str) -> bool) -> Option<bool> {
+    Some(!same(published?, installed?))
+}
+
+// reinstall when the manifest's archive for the installed version is no longer the one it was installed from,
+// told by its checksum, the exact release version or the url it is downloaded from
+fn upgrade_installation(installation: &Installation, x: &Fli) -> Result<String, String> {
+    let manifest_options = get_manifest_options(x);
+    let package = find_provider(&installation.package)
+        .ok_or_else(|| format!("Unknown package {}", installation.package))?;
+    let platform = get_platform_os().ok_or("Platform not supported")?;
+    let remote = package.list_remote_versions(&platform, &manifest_options)?;
+    let (_, release) = remote
+        .find(&installation.version)
+        .ok_or_else(|| format!("Version {} is no longer in the release manifest", installation.version))?;
+    let checks = [
+        differs(release.sha256.as_deref(), Some(&installation.archive_sha256), |a, b| a.eq_ignore_ascii_case(b)),
+        differs(release.version.as_deref(), installation.release_version.as_deref(), same_version),
+        differs(Some(&release.url), installation.url.as_deref(), |a, b| a == b),
+    ];
+    if checks.iter().all(|check| check.is_none()) {
+        return Ok("No checksum published, can't tell if it is up to date".yellow().to_string());
+    }
+    if !checks.contains(&Some(true)) {
+        return Ok("Up to date".green().to_string());
+    }
+
+    get_app(package, vec![installation.version.clone()], &manifest_options, &DownloadOptions::from_config())?;
+    let mut app = Package::new(package);
+    app.load_local_versions();
+    let options = ExtractOptions::from_config().with_strip_components(installation.strip_components);
+    let backup = app
+        .install_version(&installation.version, &installation.path, &options, true)
+        .map_err(|e| e.to_string())?;
+    print_backup(backup);
+    Ok(format!("Upgraded to {}", release.version.as_deref().unwrap_or("the latest build")).green().to_string())
+}
+
+// upgrade every recorded install, or only the one at --path
+pub fn upgrade_installs(x: &Fli) {
+    let registry = match Registry::load() {
+        Ok(registry) => registry,
+        Err(e) => {
+            println!("❌ {}", e.red());
+            return;
+        }
+    };
+    let installations: Vec<&Installation> = match x.get_values("path".to_owned()) {
+        Ok(path) => match registry.find(Path::new(path.first().unwrap())) {
+            Some(installation) => vec![installation],
+            None => {
+                println!("❌ {} {}", "Nothing was installed by xupg at".red(), path.first().unwrap());
+                return;
+            }
+        },
+        Err(_) => registry.installations.iter().collect(),
+    };
+    if installations.is_empty() {
+        println!("Nothing installed yet");
+        return;
+    }
+
+    for installation in installations {
+        println!(
+            "{} {} at {}",
+            installation.package.to_uppercase().bold().blue(),
+            installation.version.bold().blue(),
+            installation.path
+        );
+        match upgrade_installation(installation, x) {
+            Ok(status) => println!("  {}", status),
+            Err(e) => println!("  ❌ {}", e.red()),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use colored::Colorize;
+use fli::Fli;
+
+use crate::{
+    commands::{get_manifest_options, report_unrecognized},
+    helpers::{
+        api::ReleaseInfo,
+        archive::check_archive,
+        file::{verify_file, ArchiveMetadata},
+        get_platform_os,
+        print_table,
+    },
+    providers::providers,
+};
+
+// check a downloaded archive against its manifest entry (when known) and make sure it can be read,
+// returns the status to show. without a published checksum the one recorded in its sidecar when it
+// was downloaded is used, and so it is when the manifest has since moved on to another build
+pub fn check_download(file: &Path, release: Option<&ReleaseInfo>) -> Result<String, String> {
+    let metadata = ArchiveMetadata::read(file);
+    // an older build of the entry isn't corrupt, it just isn't what the manifest publishes now
+    let outdated = match (release, &metadata) {
+        (Some(release), Some(metadata)) => !metadata.is_build(&release.url, release.version.as_deref()),
+        _ => false,
+    };
+    let release = release.filter(|_| !outdated);
+    let sha256 = release
+        .and_then(|r| r.sha256.clone())
+        .or_else(|| metadata.map(|metadata| metadata.sha256).filter(|sha256| !sha256.is_empty()));
+    verify_file(file, sha256.as_deref(), release.and_then(|r| r.size)).map_err(|e| e.to_string())?;
+    check_archive(file).map_err(|e| e.to_string())?;
+    let status = match (outdated, sha256) {
+        (true, _) => "Outdated, a newer build is published".yellow(),
+        (false, Some(_)) => "OK".green(),
+        (false, None) => "OK (no checksum)".green(),
+    };
+    Ok(status.to_string())
+}
+
+pub fn verify_downloads(x: &Fli) {
+    let platform = get_platform_os().unwrap_or_default();
+    let manifest_options = get_manifest_options(x);
+
+    let headers = ["Package".to_string(), "Version".to_string(), "Status".to_string()];
+    let mut table_data: Vec<[String; 3]> = Vec::new();
+    let mut failed = 0;
+    for package in providers() {
+        let (versions, unrecognized) = package.scan_local_versions();
+        report_unrecognized(&unrecognized);
+        if versions.is_empty() {
+            continue;
+        }
+        let tool = match package.list_remote_versions(&platform, &manifest_options) {
+            Ok(tool) => Some(tool),
+            Err(e) => {
+                println!(
+                    "⚠️  {} {}",
+                    format!("{} releases unavailable, only checking archives can be read:", package.display_name()).yellow(),
+                    e.dimmed()
+                );
+                None
+            }
+        };
+        for version in versions {
+            let release = tool.as_ref().and_then(|t| t.find(version.get_version())).map(|(_, release)| release);
+            let status = match check_download(Path::new(version.get_location()), release) {
+                Ok(status) => status,
+                Err(e) => {
+                    failed += 1;
+                    e.red().to_string()
+                }
+            };
+            table_data.push([package.display_name().to_string(), version.get_version().to_string(), status]);
+        }
+    }
+
+    if table_data.is_empty() {
+        println!("No downloads to verify");
+        return;
+    }
+    print_table(headers, &table_data);
+    if failed > 0 {
+        println!("\n❌ {} {}", failed.to_string().bold().red(), "download(s) failed verification, get them again to replace them".red());
+    } else {
+        println!("\n✅ All downloads verified successfully");
+    }
+}
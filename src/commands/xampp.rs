@@ -1,7 +1,10 @@
 use colored::Colorize;
 use fli::Fli;
 
-use crate::commands::php::install_php_version;
+use crate::{
+    commands::{get_extract_options, get_manifest_options, install::{install_package_version, print_backup, print_detected}},
+    providers::php::Php,
+};
 
 pub fn set_xampp_php(x: &Fli) {
     let target_path = match x.get_values("path".to_owned()) {
@@ -25,10 +28,22 @@ pub fn set_xampp_php(x: &Fli) {
         version.bold().blue(),
         target_path.bold().blue()
     );
-    if let Err(e) = install_php_version(&version, &target_path) {
-        //color in grey or gray or light black
-        println!("❌ {}: {}", "Failed to install PHP version".red(), format!("{}", e).dimmed());
-        return;
+    let options = match get_extract_options(x) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("❌ {}", e.red());
+            return;
+        }
+    };
+    match install_package_version(&Php, &version, Some(&target_path), &options, &get_manifest_options(x)) {
+        Ok((version, target_path, backup)) => {
+            println!("✅ PHP version {} installed successfully", version);
+            print_detected(&Php, &target_path);
+            print_backup(backup);
+        }
+        Err(e) => {
+            //color in grey or gray or light black
+            println!("❌ {}: {}", "Failed to install PHP version".red(), format!("{}", e).dimmed());
+        }
     }
-    println!("✅ PHP version {} installed successfully", version);
 }
\ No newline at end of file
@@ -1,33 +1,562 @@
-use serde::Deserialize;
-use std::collections::HashMap;
+use cached::proc_macro::cached;
+use colored::Colorize;
+use minisign_verify::{PublicKey, Signature};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-#[derive(Debug, Deserialize)]
+use super::{
+    config::get_config,
+    file::get_xupg_dir,
+    http::http_client,
+    version::{same_version, sort_versions_desc, SemVer, VersionReq},
+};
+
+// the schema version this build of xupg writes and understands
+// v1 (legacy, no `schema_version`): tool -> version -> release, or tool -> release
+// v2: { "schema_version": 2, "platforms": { platform -> tool -> version -> release } }
+pub const MANIFEST_SCHEMA_VERSION: u64 = 2;
+
+// tools listed under this platform are available on every platform
+pub const ANY_PLATFORM: &str = "any";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ReleaseInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     pub url: String,
     pub release_date: String,
+    // hex encoded sha256 and size in bytes of the artifact at `url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    // long term support release, picked by the "lts" constraint
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lts: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct ToolVersions {
     #[serde(flatten)]
     pub versions: HashMap<String, ReleaseInfo>,
 }
 
-#[derive(Debug, Deserialize)]
+impl ToolVersions {
+    // the release for a version, matched as a version so "8.1.0" finds the "8.1" key
+    // and by the exact version a release names, so "8.1.26" finds it too
+    pub fn find(&self, version: &str) -> Option<(&String, &ReleaseInfo)> {
+        self.versions
+            .get_key_value(version)
+            .or_else(|| self.versions.iter().find(|(key, _)| same_version(key, version)))
+            .or_else(|| {
+                self.versions
+                    .iter()
+                    .find(|(_, info)| info.version.as_deref().is_some_and(|exact| same_version(exact, version)))
+            })
+    }
+
+    // the newest release satisfying a constraint like "^8.1" or "latest". a release is
+    // matched by the exact version it names when that matches, by its key otherwise
+    pub fn resolve(&self, requirement: &VersionReq) -> Option<(&String, &ReleaseInfo)> {
+        let candidates = self.versions.iter().filter_map(|(key, info)| {
+            let exact = info.version.as_deref().and_then(|exact| exact.parse::<SemVer>().ok());
+            let version = match exact {
+                Some(exact) if requirement.matches(&exact) => exact,
+                _ => key.parse::<SemVer>().ok()?,
+            };
+            Some((version, info.lts, (key, info)))
+        });
+        requirement.resolve(candidates).map(|(_, release)| release)
+    }
+
+    // newest first
+    pub fn sorted(&self) -> Vec<(&String, &ReleaseInfo)> {
+        let mut versions: Vec<(&String, &ReleaseInfo)> = self.versions.iter().collect();
+        sort_versions_desc(&mut versions, |(key, _)| key.as_str());
+        versions
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Platform {
     #[serde(flatten)]
     pub tools: HashMap<String, ToolVersions>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Releases {
-    #[serde(flatten)]
+    pub schema_version: u64,
     pub platforms: HashMap<String, Platform>,
 }
 
+impl Releases {
+    // get the versions of a tool for a platform, falling back to the platform independent list
+    pub fn get_tool(&self, platform: &str, tool: &str) -> Option<&ToolVersions> {
+        self.platforms
+            .get(platform)
+            .and_then(|p| p.tools.get(tool))
+            .or_else(|| self.platforms.get(ANY_PLATFORM).and_then(|p| p.tools.get(tool)))
+    }
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Fetch(String),
+    InvalidJson(String),
+    UnsupportedSchema(u64),
+    AllSourcesFailed(Vec<String>),
+    Signature(String),
+    InvalidKey { key: String, reason: String },
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Fetch(e) => write!(f, "Failed to fetch release manifest: {}", e),
+            ManifestError::InvalidJson(e) => write!(f, "Release manifest is not valid JSON: {}", e),
+            ManifestError::UnsupportedSchema(v) => write!(
+                f,
+                "Release manifest schema version {} is not supported (expected at most {}), try updating xupg",
+                v, MANIFEST_SCHEMA_VERSION
+            ),
+            ManifestError::AllSourcesFailed(errors) => {
+                write!(f, "No release manifest source could be used:\n  {}", errors.join("\n  "))
+            }
+            ManifestError::Signature(e) => write!(f, "Release manifest signature check failed: {}", e),
+            ManifestError::InvalidKey { key, reason } => {
+                write!(f, "Invalid release manifest entry `{}`: {}", key, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+fn invalid(key: &str, reason: impl Display) -> ManifestError {
+    ManifestError::InvalidKey {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn as_object<'a>(key: &str, value: &'a Value) -> Result<&'a Map<String, Value>, ManifestError> {
+    value
+        .as_object()
+        .ok_or_else(|| invalid(key, format!("expected an object, found {}", json_type(value))))
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn parse_release(key: &str, value: &Value) -> Result<ReleaseInfo, ManifestError> {
+    as_object(key, value)?;
+    ReleaseInfo::deserialize(value).map_err(|e| invalid(key, e))
+}
+
+// a release entry is an object carrying a `url`, anything else is a map of versions
+fn is_release(value: &Value) -> bool {
+    value.get("url").is_some_and(|url| url.is_string())
+}
+
+// the version key used when a tool only lists a single release, e.g 5.2.1 -> 5.2
+fn version_key(key: &str, release: &ReleaseInfo) -> Result<String, ManifestError> {
+    let version = release
+        .version
+        .as_ref()
+        .ok_or_else(|| invalid(key, "single release entries must have a `version`"))?;
+    Ok(version.split('.').take(2).collect::<Vec<&str>>().join("."))
+}
+
+fn parse_tool(key: &str, value: &Value) -> Result<ToolVersions, ManifestError> {
+    let mut versions = HashMap::new();
+    if is_release(value) {
+        let release = parse_release(key, value)?;
+        versions.insert(version_key(key, &release)?, release);
+        return Ok(ToolVersions { versions });
+    }
+    for (version, release) in as_object(key, value)? {
+        let release_key = format!("{}.{}", key, version);
+        versions.insert(version.to_string(), parse_release(&release_key, release)?);
+    }
+    Ok(ToolVersions { versions })
+}
+
+fn parse_platform(key: &str, value: &Value) -> Result<Platform, ManifestError> {
+    let mut tools = HashMap::new();
+    for (tool, versions) in as_object(key, value)? {
+        let tool_key = if key.is_empty() {
+            tool.to_string()
+        } else {
+            format!("{}.{}", key, tool)
+        };
+        tools.insert(tool.to_lowercase(), parse_tool(&tool_key, versions)?);
+    }
+    Ok(Platform { tools })
+}
+
+// tools v1 manifests only ever listed Windows builds of (the Win32 zips XAMPP uses)
+const LEGACY_WINDOWS_TOOLS: [&str; 2] = ["php", "mysql"];
+
+// v1 manifests have no platform level. their PHP and MySQL builds are Windows only,
+// everything else in them is treated as platform independent
+fn migrate_v1(value: &Value) -> Result<Releases, ManifestError> {
+    let mut platforms: HashMap<String, Platform> = HashMap::new();
+    for (tool, versions) in parse_platform("", value)?.tools {
+        let platform = match LEGACY_WINDOWS_TOOLS.contains(&tool.as_str()) {
+            true => "windows",
+            false => ANY_PLATFORM,
+        };
+        platforms
+            .entry(platform.to_string())
+            .or_insert_with(|| Platform { tools: HashMap::new() })
+            .tools
+            .insert(tool, versions);
+    }
+    Ok(Releases {
+        schema_version: MANIFEST_SCHEMA_VERSION,
+        platforms,
+    })
+}
+
+fn parse_v2(value: &Value) -> Result<Releases, ManifestError> {
+    let root = as_object("", value)?;
+    let platforms_value = root
+        .get("platforms")
+        .ok_or_else(|| invalid("platforms", "missing"))?;
+    let mut platforms = HashMap::new();
+    for (platform, tools) in as_object("platforms", platforms_value)? {
+        let key = format!("platforms.{}", platform);
+        platforms.insert(platform.to_lowercase(), parse_platform(&key, tools)?);
+    }
+    Ok(Releases {
+        schema_version: MANIFEST_SCHEMA_VERSION,
+        platforms,
+    })
+}
+
+pub fn parse_releases(body: &str) -> Result<Releases, ManifestError> {
+    let value: Value =
+        serde_json::from_str(body).map_err(|e| ManifestError::InvalidJson(e.to_string()))?;
+    as_object("", &value)?;
+    match value.get("schema_version") {
+        None => migrate_v1(&value),
+        Some(version) => match version.as_u64() {
+            Some(MANIFEST_SCHEMA_VERSION) => parse_v2(&value),
+            Some(1) => {
+                let mut legacy = value.clone();
+                legacy.as_object_mut().unwrap().remove("schema_version");
+                migrate_v1(&legacy)
+            }
+            Some(v) => Err(ManifestError::UnsupportedSchema(v)),
+            None => Err(invalid(
+                "schema_version",
+                format!("expected a number, found {}", json_type(version)),
+            )),
+        },
+    }
+}
+
+// where a manifest can be read from, either a url or a local file (`file://` or a plain path)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManifestSource {
+    Http(String),
+    File(PathBuf),
+}
+
+impl ManifestSource {
+    pub fn parse(source: &str) -> Result<Self, ManifestError> {
+        let source = source.trim();
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Ok(ManifestSource::Http(source.to_string()));
+        }
+        if source.starts_with("file://") {
+            let url = reqwest::Url::parse(source)
+                .map_err(|e| ManifestError::Fetch(format!("{}: {}", source, e)))?;
+            let path = url
+                .to_file_path()
+                .map_err(|_| ManifestError::Fetch(format!("{}: not a valid file url", source)))?;
+            return Ok(ManifestSource::File(path));
+        }
+        if source.contains("://") {
+            return Err(ManifestError::Fetch(format!("{}: unsupported manifest source", source)));
+        }
+        Ok(ManifestSource::File(PathBuf::from(source)))
+    }
+
+    pub fn read(&self) -> Result<String, ManifestError> {
+        match self {
+            ManifestSource::Http(_) => self.read_with(&http_client().map_err(ManifestError::Fetch)?),
+            ManifestSource::File(path) => read_source_file(path),
+        }
+    }
+
+    // read with `client` instead of the one built from the config, files are read the same either way
+    pub fn read_with(&self, client: &Client) -> Result<String, ManifestError> {
+        match self {
+            ManifestSource::Http(url) => client
+                .get(url)
+                .send()
+                .and_then(|response| response.error_for_status())
+                .and_then(|response| response.text())
+                .map_err(|e| ManifestError::Fetch(e.to_string())),
+            ManifestSource::File(path) => read_source_file(path),
+        }
+    }
+}
+
+fn read_source_file(path: &Path) -> Result<String, ManifestError> {
+    std::fs::read_to_string(path).map_err(|e| ManifestError::Fetch(format!("{}: {}", path.display(), e)))
+}
+
+impl ManifestSource {
+    // the detached minisign signature published next to the manifest
+    pub fn signature(&self) -> ManifestSource {
+        match self {
+            ManifestSource::Http(url) => ManifestSource::Http(format!("{}.minisig", url)),
+            ManifestSource::File(path) => {
+                let mut path = path.clone().into_os_string();
+                path.push(".minisig");
+                ManifestSource::File(PathBuf::from(path))
+            }
+        }
+    }
+}
+
+impl Display for ManifestSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestSource::Http(url) => write!(f, "{}", url),
+            ManifestSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+// public key baked in at build time, e.g XUPG_MANIFEST_PUBLIC_KEY=RWT... cargo build --release
+pub const EMBEDDED_PUBLIC_KEY: Option<&str> = option_env!("XUPG_MANIFEST_PUBLIC_KEY");
+
+// the key from the user config wins over the one embedded in the binary
+pub fn get_public_key() -> Option<String> {
+    get_config()
+        .public_key
+        .clone()
+        .or_else(|| EMBEDDED_PUBLIC_KEY.map(|key| key.to_string()))
+        .filter(|key| !key.trim().is_empty())
+}
+
+// check the manifest bytes against a minisign signature, the key can be the bare base64 key
+// or the full content of a minisign `.pub` file
+pub fn verify_manifest(body: &str, signature: &str, public_key: &str) -> Result<(), ManifestError> {
+    let public_key = public_key.trim();
+    let public_key = if public_key.contains('\n') {
+        PublicKey::decode(public_key)
+    } else {
+        PublicKey::from_base64(public_key)
+    }
+    .map_err(|e| ManifestError::Signature(format!("invalid public key: {}", e)))?;
+    let signature = Signature::decode(signature)
+        .map_err(|e| ManifestError::Signature(format!("invalid signature: {}", e)))?;
+    public_key
+        .verify(body.as_bytes(), &signature, false)
+        .map_err(|e| ManifestError::Signature(e.to_string()))
+}
+
+// `public_key` is the key the manifest has to be signed with, None skips the check
+pub fn fetch_releases_from(source: &ManifestSource, public_key: Option<&str>) -> Result<Releases, ManifestError> {
+    let body = source.read()?;
+    if let Some(public_key) = public_key {
+        let signature = source.signature().read().map_err(|e| {
+            ManifestError::Signature(format!("could not read {}: {}", source.signature(), e))
+        })?;
+        verify_manifest(&body, &signature, public_key)?;
+    }
+    parse_releases(&body)
+}
 
-pub fn fetch_releases() -> Result<Releases, reqwest::Error> {
-    let url = "https://codad5.github.io/xupg-rs/api/releases.json";
-    let releases = reqwest::blocking::get(url)?.json::<Releases>()?;
-    Ok(releases)
-}
\ No newline at end of file
+// try every configured source in order, the first one that verifies and parses wins
+pub fn fetch_releases(public_key: Option<&str>) -> Result<Releases, ManifestError> {
+    let sources = &get_config().manifest_sources;
+    let mut errors = Vec::new();
+    for source in sources {
+        let result = ManifestSource::parse(source).and_then(|source| fetch_releases_from(&source, public_key));
+        match result {
+            Ok(releases) => return Ok(releases),
+            Err(e) if sources.len() == 1 => return Err(e),
+            Err(e) => errors.push(format!("{}: {}", source, e)),
+        }
+    }
+    Err(ManifestError::AllSourcesFailed(errors))
+}
+
+// how `load_releases` is allowed to get the manifest
+#[derive(Debug, Clone, Default)]
+pub struct ManifestOptions {
+    // skip the on disk cache even when it is still fresh
+    pub refresh: bool,
+    // accept manifests that are unsigned or fail signature verification
+    pub insecure: bool,
+}
+
+// the last manifest that was fetched successfully, stored in the normalized (latest schema) form
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedReleases {
+    fetched_at: u64,
+    // false when the manifest was fetched with --insecure
+    #[serde(default)]
+    verified: bool,
+    // the `manifest_sources` it was fetched with, a copy from other sources isn't used
+    #[serde(default)]
+    sources: Vec<String>,
+    releases: Releases,
+}
+
+pub fn get_manifest_cache_path() -> PathBuf {
+    get_xupg_dir().join("cache").join("releases.json")
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// e.g 45s, 12m, 3h, 2d
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+// unverified copies are only handed out to --insecure runs, and
+// only copies fetched from the sources configured now are
+fn read_cached_releases(insecure: bool) -> Option<CachedReleases> {
+    let content = fs::read_to_string(get_manifest_cache_path()).ok()?;
+    let cache: CachedReleases = serde_json::from_str(&content).ok()?;
+    if (!cache.verified && !insecure) || cache.sources != get_config().manifest_sources {
+        return None;
+    }
+    Some(cache)
+}
+
+fn write_cached_releases(releases: &Releases, verified: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let path = get_manifest_cache_path();
+    fs::create_dir_all(path.parent().unwrap())?;
+    let cached = CachedReleases {
+        fetched_at: now_secs(),
+        verified,
+        sources: get_config().manifest_sources.clone(),
+        releases: releases.clone(),
+    };
+    // write next to the cache and rename so a crash never leaves half a manifest behind
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(&cached)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+// get the release manifest, using the on disk copy while it is younger than `cache_ttl`
+// and falling back to it (whatever its age) when every source is unreachable
+#[cached(result = true, key = "(bool, bool)", convert = r#"{ (options.refresh, options.insecure) }"#)]
+pub fn load_releases(options: &ManifestOptions) -> Result<Releases, ManifestError> {
+    // without a key nothing can be verified, only --insecure may go on without one
+    let public_key = match (get_public_key(), options.insecure) {
+        (_, true) => None,
+        (Some(key), false) => Some(key),
+        (None, false) => {
+            return Err(ManifestError::Signature(
+                "no public key configured, set `public_key` in the config or pass --insecure".to_string(),
+            ))
+        }
+    };
+    let cache = read_cached_releases(options.insecure);
+    let ttl = get_config().cache_ttl;
+    if let Some(cache) = &cache {
+        let age = now_secs().saturating_sub(cache.fetched_at);
+        if !options.refresh && age < ttl {
+            return Ok(cache.releases.clone());
+        }
+    }
+
+    if options.insecure {
+        eprintln!("⚠️  {}", "--insecure: the release manifest signature is not checked".yellow());
+    }
+    match fetch_releases(public_key.as_deref()) {
+        Ok(releases) => {
+            if let Err(e) = write_cached_releases(&releases, !options.insecure) {
+                eprintln!("⚠️  {} {}", "Failed to cache release manifest:".yellow(), e);
+            }
+            Ok(releases)
+        }
+        Err(e) => match cache {
+            Some(cache) => {
+                let age = now_secs().saturating_sub(cache.fetched_at);
+                eprintln!(
+                    "⚠️  {} {}",
+                    format!("Using cached release manifest from {} ago, it may be out of date.", format_age(age)).yellow(),
+                    format!("({})", e).dimmed()
+                );
+                Ok(cache.releases)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_v1_php_builds_to_windows() {
+        let v1 = r#"{
+            "php": {"8.3": {"version": "8.3.0", "url": "https://windows.php.net/php-8.3.0-Win32-vs16-x64.zip", "release_date": "2023-11-23"}},
+            "phpmyadmin": {"5.2": {"version": "5.2.1", "url": "https://files.phpmyadmin.net/phpMyAdmin-5.2.1-all-languages.zip", "release_date": "2023-02-08"}}
+        }"#;
+        let releases = parse_releases(v1).unwrap();
+        assert_eq!(releases.schema_version, MANIFEST_SCHEMA_VERSION);
+        assert!(releases.get_tool("windows", "php").is_some());
+        assert!(releases.get_tool("linux", "php").is_none());
+        assert!(releases.get_tool("macos", "php").is_none());
+        assert!(releases.get_tool("linux", "phpmyadmin").is_some());
+        assert!(releases.get_tool("windows", "phpmyadmin").is_some());
+    }
+
+    #[test]
+    fn resolves_by_the_exact_release_version() {
+        let manifest = r#"{"schema_version": 2, "platforms": {"windows": {"php": {
+            "8.1": {"version": "8.1.26", "url": "https://example.com/php-8.1.26.zip", "release_date": "2023-12-21"},
+            "8.3": {"version": "8.3.0", "url": "https://example.com/php-8.3.0.zip", "release_date": "2023-11-23"}
+        }}}}"#;
+        let releases = parse_releases(manifest).unwrap();
+        let php = releases.get_tool("windows", "php").unwrap();
+        let resolve = |requirement: &str| php.resolve(&requirement.parse().unwrap()).map(|(key, _)| key.clone());
+        assert_eq!(resolve("8.1.26").as_deref(), Some("8.1"));
+        assert_eq!(resolve("8.1").as_deref(), Some("8.1"));
+        assert_eq!(resolve("8.1.0").as_deref(), Some("8.1"));
+        assert_eq!(resolve(">8.1.20 <8.3").as_deref(), Some("8.1"));
+        assert_eq!(resolve("latest").as_deref(), Some("8.3"));
+        assert_eq!(resolve("8.1.27"), None);
+        assert_eq!(php.find("8.1.26").map(|(key, _)| key.as_str()), Some("8.1"));
+        assert_eq!(php.find("8.3.0").map(|(key, _)| key.as_str()), Some("8.3"));
+        assert!(php.find("8.2").is_none());
+    }
+}
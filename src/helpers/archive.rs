@@ -0,0 +1,759 @@
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{BufReader, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use indicatif::{ProgressBar, ProgressStyle};
+use xz2::read::XzDecoder;
+
+use super::{config::get_config, file::parse_size};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+    TarXz,
+    TarBz2,
+}
+
+// compound extensions come first so "php-8.3.tar.gz" isn't read as a plain ".gz"
+const EXTENSIONS: [(&str, ArchiveKind); 7] = [
+    ("tar.gz", ArchiveKind::TarGz),
+    ("tar.xz", ArchiveKind::TarXz),
+    ("tar.bz2", ArchiveKind::TarBz2),
+    ("tgz", ArchiveKind::TarGz),
+    ("txz", ArchiveKind::TarXz),
+    ("tbz2", ArchiveKind::TarBz2),
+    ("zip", ArchiveKind::Zip),
+];
+
+impl ArchiveKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        find_extension(name).map(|(_, kind)| kind)
+    }
+
+    pub fn from_magic(header: &[u8]) -> Option<Self> {
+        if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            Some(ArchiveKind::Zip)
+        } else if header.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveKind::TarGz)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(ArchiveKind::TarXz)
+        } else if header.starts_with(b"BZh") {
+            Some(ArchiveKind::TarBz2)
+        } else {
+            None
+        }
+    }
+
+    // the magic bytes win over the file name, mirrors don't always name files correctly
+    pub fn detect(path: &Path) -> Result<Self, ArchiveError> {
+        let mut header = [0u8; 6];
+        let mut file = File::open(path)?;
+        let read = file.read(&mut header)?;
+        if let Some(kind) = Self::from_magic(&header[..read]) {
+            return Ok(kind);
+        }
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        Self::from_name(&name).ok_or(ArchiveError::UnknownFormat(path.display().to_string()))
+    }
+}
+
+// the archive extension at the end of a file name or url, e.g "tar.gz" for "php-8.3.tar.gz"
+pub fn archive_extension(name: &str) -> Option<&'static str> {
+    find_extension(name).map(|(ext, _)| ext)
+}
+
+fn find_extension(name: &str) -> Option<(&'static str, ArchiveKind)> {
+    let name = name.to_lowercase();
+    EXTENSIONS.into_iter().find(|(ext, _)| name.ends_with(&format!(".{}", ext)))
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(String),
+    UnknownFormat(String),
+    Corrupt(String),
+    UnsafeEntry { entry: String, reason: String },
+    TooLarge(u64),
+    Conflict(String),
+}
+
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::Io(e) => write!(f, "Failed to extract archive: {}", e),
+            ArchiveError::UnknownFormat(path) => write!(f, "{} is not a supported archive", path),
+            ArchiveError::Corrupt(e) => write!(f, "Corrupt archive: {}", e),
+            ArchiveError::UnsafeEntry { entry, reason } => write!(f, "Refusing to extract {}: it {}", entry, reason),
+            ArchiveError::TooLarge(max) => {
+                write!(f, "Archive expands to more than {} MB, raise max_extract_size to allow it", max / 1024 / 1024)
+            }
+            ArchiveError::Conflict(e) => write!(f, "Failed to extract archive: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<std::io::Error> for ArchiveError {
+    fn from(e: std::io::Error) -> Self {
+        ArchiveError::Io(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for ArchiveError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => ArchiveError::Io(e.to_string()),
+            e => ArchiveError::Corrupt(e.to_string()),
+        }
+    }
+}
+
+// moves the progress bar along as the compressed file is read
+struct ProgressReader<R: Read> {
+    inner: R,
+    pb: ProgressBar,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pb.inc(n as u64);
+        Ok(n)
+    }
+}
+
+fn tar_archive<'a>(kind: ArchiveKind, reader: impl Read + 'a) -> tar::Archive<Box<dyn Read + 'a>> {
+    let decoder: Box<dyn Read + 'a> = match kind {
+        ArchiveKind::TarXz => Box::new(XzDecoder::new(reader)),
+        ArchiveKind::TarBz2 => Box::new(BzDecoder::new(reader)),
+        _ => Box::new(GzDecoder::new(reader)),
+    };
+    tar::Archive::new(decoder)
+}
+
+// settings for a single extraction
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    // most bytes the archive may expand to, stops zip bombs from filling the disk
+    pub max_size: u64,
+    // leading path components dropped from every entry, `None` strips a single common root directory
+    pub strip_components: Option<usize>,
+}
+
+impl ExtractOptions {
+    pub fn from_config() -> Self {
+        let config = get_config();
+        ExtractOptions {
+            max_size: parse_size(&config.max_extract_size).unwrap_or(u64::MAX),
+            strip_components: None,
+        }
+    }
+
+    pub fn with_strip_components(mut self, strip_components: Option<usize>) -> Self {
+        self.strip_components = strip_components;
+        self
+    }
+}
+
+fn unsafe_entry(entry: &Path, reason: &str) -> ArchiveError {
+    ArchiveError::UnsafeEntry {
+        entry: entry.display().to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// writes archive entries below `root`, refusing anything that would end up outside of it
+struct Extractor {
+    root: PathBuf,
+    strip: usize,
+    max_size: u64,
+    written: u64,
+    // directory modes are applied last so a read-only directory doesn't block its own entries
+    dir_modes: Vec<(PathBuf, u32)>,
+    // symlinks created so far, checked again once every entry is in place
+    symlinks: Vec<PathBuf>,
+}
+
+impl Extractor {
+    fn new(dest: &Path, strip: usize, options: &ExtractOptions) -> Result<Self, ArchiveError> {
+        std::fs::create_dir_all(dest)?;
+        Ok(Extractor {
+            root: dest.canonicalize()?,
+            strip,
+            max_size: options.max_size,
+            written: 0,
+            dir_modes: Vec::new(),
+            symlinks: Vec::new(),
+        })
+    }
+
+    // where an entry goes, `None` for entries that only name the root itself or are stripped away
+    fn entry_path(&self, name: &Path) -> Result<Option<PathBuf>, ArchiveError> {
+        let mut relative = PathBuf::new();
+        let mut skipped = 0;
+        for component in name.components() {
+            match component {
+                Component::Normal(_) if skipped < self.strip => skipped += 1,
+                Component::Normal(part) => relative.push(part),
+                Component::CurDir => {}
+                Component::ParentDir => return Err(unsafe_entry(name, "contains \"..\"")),
+                Component::RootDir | Component::Prefix(_) => return Err(unsafe_entry(name, "is an absolute path")),
+            }
+        }
+        if relative.as_os_str().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(self.root.join(relative)))
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    // create the directories leading to `dir`, every existing step (symlinks included) must stay inside the root
+    fn ensure_dir(&self, dir: &Path) -> Result<(), ArchiveError> {
+        let mut current = self.root.clone();
+        for part in self.relative(dir).components() {
+            current.push(part);
+            match current.symlink_metadata() {
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    let resolved = current.canonicalize()?;
+                    if !resolved.starts_with(&self.root) {
+                        return Err(unsafe_entry(self.relative(dir), "goes through a symlink pointing outside the target directory"));
+                    }
+                    if !resolved.is_dir() {
+                        return Err(ArchiveError::Conflict(format!("{} is not a directory", current.display())));
+                    }
+                }
+                Ok(meta) if meta.is_dir() => {}
+                Ok(_) => return Err(ArchiveError::Conflict(format!("{} is not a directory", current.display()))),
+                Err(_) => std::fs::create_dir(&current)?,
+            }
+        }
+        Ok(())
+    }
+
+    // make room for a new entry at `path`, existing files and symlinks are replaced but never followed
+    fn prepare(&self, path: &Path) -> Result<(), ArchiveError> {
+        if let Some(parent) = path.parent() {
+            self.ensure_dir(parent)?;
+        }
+        match path.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => Err(ArchiveError::Conflict(format!("{} is an existing directory", path.display()))),
+            Ok(_) => Ok(std::fs::remove_file(path)?),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: Option<u32>) -> Result<(), ArchiveError> {
+        self.ensure_dir(path)?;
+        if let Some(mode) = mode {
+            self.dir_modes.push((path.to_path_buf(), mode));
+        }
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &Path, reader: &mut impl Read, mode: Option<u32>) -> Result<(), ArchiveError> {
+        self.prepare(path)?;
+        let mut out = OpenOptions::new().write(true).create_new(true).open(path)?;
+        // read one byte past the limit so going over it can be told apart from hitting it exactly
+        let remaining = self.max_size - self.written;
+        let copied = std::io::copy(&mut reader.take(remaining.saturating_add(1)), &mut out)?;
+        if copied > remaining {
+            drop(out);
+            std::fs::remove_file(path)?;
+            return Err(ArchiveError::TooLarge(self.max_size));
+        }
+        self.written += copied;
+        set_mode(path, mode)
+    }
+
+    fn create_symlink(&mut self, path: &Path, target: &Path) -> Result<(), ArchiveError> {
+        self.prepare(path)?;
+        // resolve the target from the real directory the link lives in, following the
+        // symlinks it goes through so "a -> ." can't be used to climb out with "a/a/../.."
+        let mut resolved = match path.parent() {
+            Some(parent) => parent.canonicalize()?,
+            None => self.root.clone(),
+        };
+        for component in target.components() {
+            match component {
+                Component::Normal(part) => {
+                    resolved.push(part);
+                    if resolved.symlink_metadata().is_ok_and(|meta| meta.file_type().is_symlink()) {
+                        resolved = resolved
+                            .canonicalize()
+                            .map_err(|_| unsafe_entry(self.relative(path), "is a symlink through a symlink that can't be resolved"))?;
+                    }
+                }
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(unsafe_entry(self.relative(path), "is a symlink to an absolute path"))
+                }
+            }
+            if !resolved.starts_with(&self.root) {
+                return Err(unsafe_entry(self.relative(path), "is a symlink pointing outside the target directory"));
+            }
+        }
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(target, path)?;
+        self.symlinks.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn create_hard_link(&self, path: &Path, source: &Path) -> Result<(), ArchiveError> {
+        let source = source.canonicalize()?;
+        if !source.starts_with(&self.root) {
+            return Err(unsafe_entry(self.relative(path), "is a hard link pointing outside the target directory"));
+        }
+        self.prepare(path)?;
+        std::fs::hard_link(source, path)?;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), ArchiveError> {
+        // a link created later can change where an earlier one leads, so every link that
+        // resolves now has to land inside the root. dangling ones don't lead anywhere
+        for link in &self.symlinks {
+            if let Ok(resolved) = link.canonicalize() {
+                if !resolved.starts_with(&self.root) {
+                    std::fs::remove_file(link)?;
+                    return Err(unsafe_entry(self.relative(link), "is a symlink pointing outside the target directory"));
+                }
+            }
+        }
+        // deepest directories first so a parent losing its write bit doesn't matter
+        for (dir, mode) in self.dir_modes.iter().rev() {
+            set_mode(dir, Some(*mode))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) -> Result<(), ArchiveError> {
+    use std::os::unix::fs::PermissionsExt;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: Option<u32>) -> Result<(), ArchiveError> {
+    Ok(())
+}
+
+fn extract_zip(file: &Path, extractor: &mut Extractor, pb: &ProgressBar) -> Result<(), ArchiveError> {
+    let mut archive = zip::ZipArchive::new(File::open(file)?)?;
+    pb.set_length(archive.len() as u64);
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = PathBuf::from(entry.name());
+        if let Some(outpath) = extractor.entry_path(&name)? {
+            if entry.is_dir() {
+                extractor.create_dir(&outpath, entry.unix_mode())?;
+            } else if entry.is_symlink() {
+                // the entry's contents are the link target
+                let mut target = String::new();
+                entry.read_to_string(&mut target)?;
+                extractor.create_symlink(&outpath, Path::new(&target))?;
+            } else {
+                let mode = entry.unix_mode();
+                extractor.write_file(&outpath, &mut entry, mode)?;
+            }
+        }
+        pb.inc(1);
+    }
+    Ok(())
+}
+
+fn extract_tar(kind: ArchiveKind, file: &Path, extractor: &mut Extractor, pb: &ProgressBar) -> Result<(), ArchiveError> {
+    let file = File::open(file)?;
+    pb.set_length(file.metadata()?.len());
+    let reader = ProgressReader { inner: BufReader::new(file), pb: pb.clone() };
+
+    let mut archive = tar_archive(kind, reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let Some(outpath) = extractor.entry_path(&name)? else {
+            continue;
+        };
+        let entry_type = entry.header().entry_type();
+        let mode = entry.header().mode().ok();
+        if entry_type.is_dir() {
+            extractor.create_dir(&outpath, mode)?;
+        } else if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let target = entry
+                .link_name()?
+                .map(|target| target.into_owned())
+                .ok_or_else(|| unsafe_entry(&name, "is a link without a target"))?;
+            if entry_type.is_symlink() {
+                extractor.create_symlink(&outpath, &target)?;
+            } else {
+                // hard link targets are other entries of the archive
+                let source = extractor
+                    .entry_path(&target)?
+                    .ok_or_else(|| unsafe_entry(&name, "is a hard link to the target directory"))?;
+                extractor.create_hard_link(&outpath, &source)?;
+            }
+        } else if entry_type.is_file() || entry_type.is_contiguous() {
+            extractor.write_file(&outpath, &mut entry, mode)?;
+        }
+        // devices, fifos and other special entries are skipped
+    }
+    Ok(())
+}
+
+// 1 when every entry sits under the same top-level directory, like "phpMyAdmin-5.2.1-all-languages/"
+fn common_root_depth(names: impl Iterator<Item = PathBuf>) -> usize {
+    let mut root: Option<OsString> = None;
+    let mut nested = false;
+    for name in names {
+        let mut parts = name.components().filter(|c| matches!(c, Component::Normal(_)));
+        let Some(first) = parts.next() else {
+            continue;
+        };
+        nested |= parts.next().is_some();
+        match &root {
+            Some(root) if root != first.as_os_str() => return 0,
+            Some(_) => {}
+            None => root = Some(first.as_os_str().to_owned()),
+        }
+    }
+    // a lone file at the top isn't a directory to strip
+    usize::from(nested)
+}
+
+fn detect_strip_components(kind: ArchiveKind, file: &Path) -> Result<usize, ArchiveError> {
+    match kind {
+        ArchiveKind::Zip => {
+            let archive = zip::ZipArchive::new(File::open(file)?)?;
+            Ok(common_root_depth(archive.file_names().map(PathBuf::from)))
+        }
+        kind => {
+            let mut archive = tar_archive(kind, BufReader::new(File::open(file)?));
+            let mut names = Vec::new();
+            for entry in archive.entries()? {
+                names.push(entry?.path()?.into_owned());
+            }
+            Ok(common_root_depth(names.into_iter()))
+        }
+    }
+}
+
+// extract any supported archive into dest, entries that would escape dest are refused
+pub fn extract(file: &Path, dest: &Path, options: &ExtractOptions, pb: &ProgressBar) -> Result<(), ArchiveError> {
+    let kind = ArchiveKind::detect(file)?;
+    let strip = match options.strip_components {
+        Some(strip) => strip,
+        None => detect_strip_components(kind, file)?,
+    };
+    let mut extractor = Extractor::new(dest, strip, options)?;
+    match kind {
+        ArchiveKind::Zip => extract_zip(file, &mut extractor, pb)?,
+        kind => extract_tar(kind, file, &mut extractor, pb)?,
+    }
+    extractor.finish()?;
+    pb.finish_with_message("Extracted archive");
+    Ok(())
+}
+
+pub fn extract_with_progress(file: &Path, dest: &Path, options: &ExtractOptions) -> Result<(), ArchiveError> {
+    let pb = ProgressBar::new(100);
+    let style = ProgressStyle::default_bar()
+        .template("[{elapsed_precise}] {bar:40.cyan/blue} {msg}")
+        .unwrap()
+        .progress_chars("##-");
+    pb.set_style(style);
+    extract(file, dest, options, &pb)
+}
+
+// read every entry of an archive so truncated or corrupted files are caught before install
+pub fn check_archive(file: &Path) -> Result<(), ArchiveError> {
+    match ArchiveKind::detect(file)? {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(file)?)?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                std::io::copy(&mut entry, &mut std::io::sink()).map_err(|e| ArchiveError::Corrupt(e.to_string()))?;
+            }
+        }
+        kind => {
+            let mut archive = tar_archive(kind, BufReader::new(File::open(file)?));
+            for entry in archive.entries().map_err(|e| ArchiveError::Corrupt(e.to_string()))? {
+                let mut entry = entry.map_err(|e| ArchiveError::Corrupt(e.to_string()))?;
+                std::io::copy(&mut entry, &mut std::io::sink()).map_err(|e| ArchiveError::Corrupt(e.to_string()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    enum Entry<'a> {
+        File(&'a str, &'a [u8]),
+        Dir(&'a str),
+        Symlink(&'a str, &'a str),
+        HardLink(&'a str, &'a str),
+    }
+
+    // a fresh directory under the system temp dir, removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("xupg-archive-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // names are written into the header as-is, the tar builder would refuse ".." and absolute paths
+    fn set_name(field: &mut [u8; 100], name: &str) {
+        field.fill(0);
+        field[..name.len()].copy_from_slice(name.as_bytes());
+    }
+
+    fn write_tar_gz(path: &Path, entries: &[Entry]) {
+        let mut builder = tar::Builder::new(GzEncoder::new(File::create(path).unwrap(), Compression::fast()));
+        for entry in entries {
+            let mut header = tar::Header::new_old();
+            let (name, data): (&str, &[u8]) = match entry {
+                Entry::File(name, data) => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    (name, data)
+                }
+                Entry::Dir(name) => {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    (name, &[])
+                }
+                Entry::Symlink(name, target) | Entry::HardLink(name, target) => {
+                    let kind = match entry {
+                        Entry::Symlink(..) => tar::EntryType::Symlink,
+                        _ => tar::EntryType::Link,
+                    };
+                    header.set_entry_type(kind);
+                    set_name(&mut header.as_old_mut().linkname, target);
+                    (name, &[])
+                }
+            };
+            set_name(&mut header.as_old_mut().name, name);
+            header.set_mode(0o755);
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap().flush().unwrap();
+    }
+
+    // names are stored as-is here too, zip has no hard links
+    fn write_zip(path: &Path, entries: &[Entry]) {
+        let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default().unix_permissions(0o755);
+        for entry in entries {
+            match entry {
+                Entry::File(name, data) => {
+                    writer.start_file(*name, options).unwrap();
+                    writer.write_all(data).unwrap();
+                }
+                Entry::Dir(name) => writer.add_directory(*name, options).unwrap(),
+                Entry::Symlink(name, target) => writer.add_symlink(*name, *target, options).unwrap(),
+                Entry::HardLink(..) => unreachable!("zip archives have no hard links"),
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    fn extract_entries(dir: &TempDir, entries: &[Entry], max_size: u64) -> Result<PathBuf, ArchiveError> {
+        let archive = dir.0.join("test.tar.gz");
+        write_tar_gz(&archive, entries);
+        extract_into(dir, &archive, max_size)
+    }
+
+    fn extract_zip_entries(dir: &TempDir, entries: &[Entry], max_size: u64) -> Result<PathBuf, ArchiveError> {
+        let archive = dir.0.join("test.zip");
+        write_zip(&archive, entries);
+        extract_into(dir, &archive, max_size)
+    }
+
+    fn extract_into(dir: &TempDir, archive: &Path, max_size: u64) -> Result<PathBuf, ArchiveError> {
+        let dest = dir.0.join("out");
+        let options = ExtractOptions { max_size, strip_components: Some(0) };
+        extract(archive, &dest, &options, &ProgressBar::hidden())?;
+        Ok(dest)
+    }
+
+    fn assert_unsafe(result: Result<PathBuf, ArchiveError>) {
+        match result {
+            Err(ArchiveError::UnsafeEntry { .. }) => {}
+            other => panic!("expected an unsafe entry error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn extracts_files_dirs_and_links() {
+        let dir = TempDir::new("ok");
+        let dest = extract_entries(
+            &dir,
+            &[
+                Entry::Dir("bin"),
+                Entry::File("bin/tool", b"hello"),
+                Entry::Symlink("tool", "bin/tool"),
+                Entry::HardLink("tool-copy", "bin/tool"),
+            ],
+            u64::MAX,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(dest.join("bin/tool")).unwrap(), b"hello");
+        assert_eq!(std::fs::read(dest.join("tool-copy")).unwrap(), b"hello");
+        #[cfg(unix)]
+        assert_eq!(std::fs::read(dest.join("tool")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let dir = TempDir::new("absolute");
+        assert_unsafe(extract_entries(&dir, &[Entry::File("/tmp/xupg-escape", b"x")], u64::MAX));
+    }
+
+    #[test]
+    fn rejects_parent_dir_entries() {
+        let dir = TempDir::new("parent");
+        assert_unsafe(extract_entries(&dir, &[Entry::File("../escape", b"x")], u64::MAX));
+        assert!(!dir.0.join("escape").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_the_target() {
+        let dir = TempDir::new("symlink");
+        assert_unsafe(extract_entries(&dir, &[Entry::Symlink("up", "..")], u64::MAX));
+        assert_unsafe(extract_entries(&dir, &[Entry::Symlink("abs", "/etc")], u64::MAX));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_writing_through_a_symlink() {
+        let dir = TempDir::new("through");
+        std::fs::create_dir_all(dir.0.join("outside")).unwrap();
+        assert_unsafe(extract_entries(
+            &dir,
+            &[Entry::Symlink("link", "../outside"), Entry::File("link/file", b"x")],
+            u64::MAX,
+        ));
+        assert!(!dir.0.join("outside/file").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlink_chains_out_of_the_target() {
+        let dir = TempDir::new("chain");
+        assert_unsafe(extract_entries(
+            &dir,
+            &[Entry::Symlink("inner", "."), Entry::Symlink("esc", "inner/inner/inner/../..")],
+            u64::MAX,
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_links_turned_outwards_by_a_later_link() {
+        // "esc" is fine on its own, it only leads out once "b" points back at the root
+        let dir = TempDir::new("later");
+        assert_unsafe(extract_entries(
+            &dir,
+            &[Entry::Symlink("esc", "b/.."), Entry::Symlink("b", ".")],
+            u64::MAX,
+        ));
+    }
+
+    #[test]
+    fn rejects_hard_links_out_of_the_target() {
+        let dir = TempDir::new("hardlink");
+        assert_unsafe(extract_entries(&dir, &[Entry::HardLink("passwd", "../../etc/passwd")], u64::MAX));
+        assert_unsafe(extract_entries(&dir, &[Entry::HardLink("passwd", "/etc/passwd")], u64::MAX));
+    }
+
+    #[test]
+    fn stops_at_max_size() {
+        let dir = TempDir::new("size");
+        let result = extract_entries(&dir, &[Entry::File("a", &[0; 600]), Entry::File("b", &[0; 600])], 1000);
+        assert!(matches!(result, Err(ArchiveError::TooLarge(1000))));
+        assert!(!dir.0.join("out/b").exists());
+
+        let dir = TempDir::new("size-exact");
+        extract_entries(&dir, &[Entry::File("a", &[0; 500]), Entry::File("b", &[0; 500])], 1000).unwrap();
+    }
+
+    #[test]
+    fn extracts_zip_files_dirs_and_links() {
+        let dir = TempDir::new("zip-ok");
+        let dest = extract_zip_entries(
+            &dir,
+            &[Entry::Dir("bin/"), Entry::File("bin/tool", b"hello"), Entry::Symlink("tool", "bin/tool")],
+            u64::MAX,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(dest.join("bin/tool")).unwrap(), b"hello");
+        #[cfg(unix)]
+        assert_eq!(std::fs::read(dest.join("tool")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn rejects_zip_absolute_paths() {
+        let dir = TempDir::new("zip-absolute");
+        assert_unsafe(extract_zip_entries(&dir, &[Entry::File("/tmp/xupg-escape", b"x")], u64::MAX));
+    }
+
+    #[test]
+    fn rejects_zip_parent_dir_entries() {
+        let dir = TempDir::new("zip-parent");
+        assert_unsafe(extract_zip_entries(&dir, &[Entry::File("../escape", b"x")], u64::MAX));
+        assert!(!dir.0.join("escape").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_zip_symlinks_out_of_the_target() {
+        let dir = TempDir::new("zip-symlink");
+        assert_unsafe(extract_zip_entries(&dir, &[Entry::Symlink("up", "..")], u64::MAX));
+        assert_unsafe(extract_zip_entries(&dir, &[Entry::Symlink("abs", "/etc")], u64::MAX));
+    }
+
+    #[test]
+    fn stops_zip_at_max_size() {
+        let dir = TempDir::new("zip-size");
+        let result = extract_zip_entries(&dir, &[Entry::File("a", &[0; 600]), Entry::File("b", &[0; 600])], 1000);
+        assert!(matches!(result, Err(ArchiveError::TooLarge(1000))));
+        assert!(!dir.0.join("out/b").exists());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    env,
+    fs,
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use colored::Colorize;
+use serde::Deserialize;
+
+use super::file::{get_xupg_dir, parse_rate, parse_size};
+
+pub const DEFAULT_MANIFEST_URL: &str = "https://codad5.github.io/xupg-rs/api/releases.json";
+pub const DEFAULT_NODE_MIRROR: &str = "https://nodejs.org/dist";
+
+// env variables that override the config file
+pub const CONFIG_ENV: &str = "XUPG_CONFIG";
+pub const MANIFEST_SOURCES_ENV: &str = "XUPG_MANIFEST_SOURCES";
+pub const CACHE_TTL_ENV: &str = "XUPG_CACHE_TTL";
+pub const PUBLIC_KEY_ENV: &str = "XUPG_PUBLIC_KEY";
+pub const RETRIES_ENV: &str = "XUPG_RETRIES";
+pub const TIMEOUT_ENV: &str = "XUPG_TIMEOUT";
+pub const JOBS_ENV: &str = "XUPG_JOBS";
+pub const NODE_MIRROR_ENV: &str = "XUPG_NODE_MIRROR";
+
+// how long a cached release manifest is used before fetching it again
+pub const DEFAULT_CACHE_TTL: u64 = 60 * 60;
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_DELAY: u64 = 1000;
+pub const DEFAULT_TIMEOUT: u64 = 300;
+pub const DEFAULT_JOBS: usize = 3;
+pub const DEFAULT_MAX_EXTRACT_SIZE: &str = "8G";
+pub const DEFAULT_SNAPSHOTS: usize = 3;
+
+// user configuration, read from ~/.xupg/config.json (or $XUPG_CONFIG)
+//
+// {
+//     "manifest_sources": ["https://mirror.internal/xupg/releases.json", "file:///opt/xupg/releases.json"],
+//     "cache_ttl": 3600,
+//     "public_key": "<your minisign public key>",
+//     "retries": 3,
+//     "retry_delay": 1000,
+//     "timeout": 300,
+//     "jobs": 3,
+//     "rate_limit": "2M",
+//     "proxy": "http://proxy.internal:3128",
+//     "no_proxy": "localhost,.internal",
+//     "ca_certs": ["/etc/ssl/certs/internal-ca.pem"],
+//     "max_extract_size": "8G",
+//     "snapshots": 3,
+//     "node_mirror": "https://nodejs.org/dist"
+// }
+// unknown keys are an error so a typo like "publickey" can't quietly turn a setting off
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // manifest locations tried in order until one succeeds
+    pub manifest_sources: Vec<String>,
+    // seconds a cached release manifest stays fresh
+    pub cache_ttl: u64,
+    // minisign public key release manifests must be signed with, overrides the embedded key
+    pub public_key: Option<String>,
+    // how many times a failed download is retried
+    pub retries: u32,
+    // milliseconds before the first retry, doubled for each retry after it
+    pub retry_delay: u64,
+    // seconds a download may stall before the attempt is abandoned
+    pub timeout: u64,
+    // how many files are downloaded at the same time
+    pub jobs: usize,
+    // combined download speed cap, e.g 500K or 2M per second
+    pub rate_limit: Option<String>,
+    // proxy used for every request instead of HTTP(S)_PROXY
+    pub proxy: Option<String>,
+    // hosts that skip `proxy`, defaults to NO_PROXY
+    pub no_proxy: Option<String>,
+    // extra PEM root certificates to trust, e.g for a TLS intercepting proxy
+    pub ca_certs: Vec<String>,
+    // most an archive may expand to when installed, e.g 500M or 8G
+    pub max_extract_size: String,
+    // how many previous installs are kept per install path for `xupg rollback`
+    pub snapshots: usize,
+    // where Node.js releases are listed (index.json) and downloaded from
+    pub node_mirror: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            manifest_sources: vec![DEFAULT_MANIFEST_URL.to_string()],
+            cache_ttl: DEFAULT_CACHE_TTL,
+            public_key: None,
+            retries: DEFAULT_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            timeout: DEFAULT_TIMEOUT,
+            jobs: DEFAULT_JOBS,
+            rate_limit: None,
+            proxy: None,
+            no_proxy: None,
+            ca_certs: Vec::new(),
+            max_extract_size: DEFAULT_MAX_EXTRACT_SIZE.to_string(),
+            snapshots: DEFAULT_SNAPSHOTS,
+            node_mirror: DEFAULT_NODE_MIRROR.to_string(),
+        }
+    }
+}
+
+pub fn get_config_path() -> PathBuf {
+    match env::var(CONFIG_ENV) {
+        Ok(path) if !path.trim().is_empty() => PathBuf::from(path),
+        _ => get_xupg_dir().join("config.json"),
+    }
+}
+
+// read a numeric setting from the environment, unset or empty means not overridden
+fn parse_env<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} must be a number, got {}", name, value)),
+        _ => Ok(None),
+    }
+}
+
+pub fn load_config() -> Result<Config, String> {
+    let path = get_config_path();
+    let mut config = if path.exists() {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        serde_json::from_str::<Config>(&content)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?
+    } else {
+        Config::default()
+    };
+
+    if let Ok(sources) = env::var(MANIFEST_SOURCES_ENV) {
+        let sources: Vec<String> = sources
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if !sources.is_empty() {
+            config.manifest_sources = sources;
+        }
+    }
+    if let Some(ttl) = parse_env(CACHE_TTL_ENV)? {
+        config.cache_ttl = ttl;
+    }
+    if let Some(retries) = parse_env(RETRIES_ENV)? {
+        config.retries = retries;
+    }
+    if let Some(timeout) = parse_env(TIMEOUT_ENV)? {
+        config.timeout = timeout;
+    }
+    if let Some(jobs) = parse_env(JOBS_ENV)? {
+        config.jobs = jobs;
+    }
+    if config.jobs == 0 {
+        return Err("jobs must be at least 1".to_string());
+    }
+    if let Some(rate) = &config.rate_limit {
+        parse_rate(rate)?;
+    }
+    if parse_size(&config.max_extract_size).is_none() {
+        return Err(format!("Invalid max_extract_size {}, expected something like 500M or 8G", config.max_extract_size));
+    }
+    if let Ok(key) = env::var(PUBLIC_KEY_ENV) {
+        if !key.trim().is_empty() {
+            config.public_key = Some(key);
+        }
+    }
+    if let Ok(mirror) = env::var(NODE_MIRROR_ENV) {
+        if !mirror.trim().is_empty() {
+            config.node_mirror = mirror.trim().to_string();
+        }
+    }
+    if config.manifest_sources.is_empty() {
+        config.manifest_sources = Config::default().manifest_sources;
+    }
+    Ok(config)
+}
+
+// the config is loaded once per run. a broken config stops xupg, running on the defaults
+// instead would drop settings like `public_key`, `proxy` and the manifest sources
+pub fn get_config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}", e.red());
+            std::process::exit(1);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(serde_json::from_str::<Config>(r#"{"publickey": "RWQ..."}"#).is_err());
+        let config: Config = serde_json::from_str(r#"{"public_key": "RWQ...", "jobs": 2}"#).unwrap();
+        assert_eq!(config.public_key.as_deref(), Some("RWQ..."));
+        assert_eq!(config.jobs, 2);
+        assert_eq!(config.timeout, DEFAULT_TIMEOUT);
+    }
+}
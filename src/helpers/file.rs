@@ -1,8 +1,18 @@
-use std::{fs::{create_dir_all, File}, io::{Read, Write}, path::{Path, PathBuf}, sync::{Arc, Mutex}, thread, time::Duration};
+use std::{collections::VecDeque, fmt::Display, fs::{create_dir_all, File, OpenOptions}, io::{Read, Write}, path::{Path, PathBuf}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
 
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 
-use reqwest::blocking::ClientBuilder;
+use rand::Rng;
+use reqwest::{blocking::{Client, Response}, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    config::get_config,
+    http::http_client,
+    store::{add_to_store, get_lock_path, restore_cached, FileLock},
+    version::same_version,
+};
 
 
 
@@ -10,15 +20,101 @@ use reqwest::blocking::ClientBuilder;
 pub struct DownloadInfo {
     pub url: String,
     pub dest: PathBuf,
+    // expected hex encoded sha256 and size in bytes, checked once the download completes
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+    // written next to the download once it is complete
+    pub metadata: Option<ArchiveMetadata>,
+}
+
+// what a downloaded archive is, kept as `<archive>.json` next to it so it is recognised without guessing from its name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveMetadata {
+    // lowercase package name, e.g "php"
+    pub package: String,
+    pub version: String,
+    pub url: String,
+    #[serde(default)]
+    pub sha256: String,
+    pub platform: String,
+    pub arch: String,
+    // the exact version the manifest says the build is, e.g "8.1.26" for "8.1"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_version: Option<String>,
+}
+
+impl ArchiveMetadata {
+    pub fn new(package: &str, version: &str, url: &str, platform: &str) -> Self {
+        ArchiveMetadata {
+            package: package.to_lowercase(),
+            version: version.to_string(),
+            url: url.to_string(),
+            sha256: String::new(),
+            platform: platform.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            release_version: None,
+        }
+    }
+
+    // whether the download is the build at `url`, and the exact `release_version` when both sides know it.
+    // a manifest entry like "8.1" moving on to a newer build changes one of them
+    pub fn is_build(&self, url: &str, release_version: Option<&str>) -> bool {
+        let same_release = match (self.release_version.as_deref(), release_version) {
+            (Some(found), Some(wanted)) => same_version(found, wanted),
+            _ => true,
+        };
+        self.url == url && same_release
+    }
+
+    pub fn with_release_version(mut self, release_version: Option<String>) -> Self {
+        self.release_version = release_version;
+        self
+    }
+
+    // the sidecar of `archive`, None when there is none or it can't be read
+    pub fn read(archive: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(get_metadata_path(archive)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn write(&self, archive: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(get_metadata_path(archive), content)
+    }
+}
+
+pub fn get_metadata_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+pub fn is_metadata_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
 }
 
 impl DownloadInfo {
     pub fn new(url: String, dest: PathBuf) -> Self {
-        Self { url, dest }
+        Self { url, dest, sha256: None, size: None, metadata: None }
+    }
+
+    pub fn with_metadata(mut self, metadata: ArchiveMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
     }
 
-    pub fn download_with_progress(&self, pb: ProgressBar) -> Result<(), Box<dyn std::error::Error>> {
-        download_with_progress(&self.url, &self.dest, pb)
+    pub fn with_integrity(mut self, sha256: Option<String>, size: Option<u64>) -> Self {
+        self.sha256 = sha256;
+        self.size = size;
+        self
+    }
+
+    pub fn verify(&self) -> Result<(), IntegrityError> {
+        verify_file(&self.dest, self.sha256.as_deref(), self.size)
+    }
+
+    pub fn download_with_progress(&self, pb: ProgressBar, options: &DownloadOptions) -> Result<(), Box<dyn std::error::Error>> {
+        download_with_progress(&self.url, &self.dest, self.size, pb, options)
     }
 
     pub fn url(&self) -> &str {
@@ -27,10 +123,22 @@ impl DownloadInfo {
 }
 
 
+// root of everything xupg stores, ~/.xupg unless XUPG_HOME is set
+pub fn get_xupg_dir() -> PathBuf {
+    if let Ok(home) = std::env::var("XUPG_HOME") {
+        if !home.trim().is_empty() {
+            return PathBuf::from(home);
+        }
+    }
+    let mut path = dirs_next::home_dir().expect("Could not determine data directory");
+    path.push(".xupg");
+    path
+}
+
 //get download path dir
 pub fn get_download_dir(app_name: &str) -> PathBuf {
-    let mut path = dirs_next::home_dir().expect("Could not determine data directory");
-    path.push(format!(".xupg/module/downloads/{}", app_name));
+    let mut path = get_xupg_dir();
+    path.push(format!("module/downloads/{}", app_name));
     path
 }
 
@@ -41,66 +149,418 @@ pub fn get_download_path(app_name: &str, file_name: &str) -> PathBuf {
     path
 }
 
-pub fn download_with_progress(url: &str, dest: &Path, pb: ProgressBar) -> Result<(), Box<dyn std::error::Error>> {
-    create_dir_all(dest.parent().unwrap())?;
+// downloads are written here first and only renamed to `dest` once complete
+pub fn get_partial_path(dest: &Path) -> PathBuf {
+    let mut path = dest.as_os_str().to_owned();
+    path.push(".part");
+    PathBuf::from(path)
+}
+
+// the ETag or Last-Modified of the response a `.part` file was started from
+fn get_validator_path(part: &Path) -> PathBuf {
+    let mut path = part.as_os_str().to_owned();
+    path.push(".validator");
+    PathBuf::from(path)
+}
+
+// an unfinished download or the validator kept next to it
+pub fn is_partial_download(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "part")
+        || path.file_name().is_some_and(|name| name.to_string_lossy().ends_with(".part.validator"))
+}
+
+// a validator `If-Range` accepts: a strong ETag, or else Last-Modified (weak ETags can't be used for ranges)
+fn response_validator(response: &Response) -> Option<String> {
+    let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok());
+    header(reqwest::header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(reqwest::header::LAST_MODIFIED))
+        .map(|validator| validator.to_string())
+}
+
+// remember what the `.part` file is a copy of, so a resume only appends to the same file
+fn save_validator(part: &Path, response: &Response) -> Result<(), DownloadError> {
+    let path = get_validator_path(part);
+    match response_validator(response) {
+        Some(validator) => std::fs::write(path, validator).map_err(fatal),
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(fatal(e)),
+            _ => Ok(()),
+        },
+    }
+}
+
+// start of a `Content-Range: bytes <start>-<end>/<total>` header
+fn content_range_start(response: &Response) -> Option<u64> {
+    let range = response.headers().get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+    let range = range.trim().strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+// settings shared by every download in a run
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    // extra attempts after the first one fails with a retryable error
+    pub retries: u32,
+    // base delay before the first retry, doubled for every retry after it
+    pub retry_delay: Duration,
+    // how many files are downloaded at the same time
+    pub jobs: usize,
+    // shared by every download so the cap applies to the whole run
+    pub limiter: Option<Arc<RateLimiter>>,
+}
+
+impl DownloadOptions {
+    pub fn from_config() -> Self {
+        let config = get_config();
+        let rate_limit = config.rate_limit.as_deref().and_then(|rate| parse_rate(rate).ok());
+        DownloadOptions {
+            retries: config.retries,
+            retry_delay: Duration::from_millis(config.retry_delay),
+            jobs: config.jobs,
+            limiter: None,
+        }
+        .with_rate_limit(rate_limit)
+    }
+
+    // cap the combined download speed in bytes per second, `None` removes the cap
+    pub fn with_rate_limit(mut self, bytes_per_sec: Option<u64>) -> Self {
+        self.limiter = bytes_per_sec
+            .filter(|rate| *rate > 0)
+            .map(|rate| Arc::new(RateLimiter::new(rate)));
+        self
+    }
+
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+}
+
+// parse a size like 500K, 2M or 1G (1024 based), a bare number is bytes
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (number, multiplier) = match size.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&size[..size.len() - 1], 1024),
+        Some('M') => (&size[..size.len() - 1], 1024 * 1024),
+        Some('G') => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| (n * multiplier as f64) as u64)
+}
+
+// parse a rate like 500K, 2M or 1G per second
+pub fn parse_rate(rate: &str) -> Result<u64, String> {
+    parse_size(rate)
+        .ok_or_else(|| format!("Invalid rate {}, expected something like 500K, 2M or 1G", rate.trim()))
+}
+
+// token bucket shared between download threads, allows bursts of up to one second worth of bytes
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    // when the bucket was last refilled and how many bytes it holds, negative while in debt
+    state: Mutex<(Instant, f64)>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter {
+            bytes_per_sec,
+            state: Mutex::new((Instant::now(), 0.0)),
+        }
+    }
+
+    // take `bytes` from the bucket, sleeping until the bucket is out of debt
+    pub fn consume(&self, bytes: u64) {
+        let rate = self.bytes_per_sec as f64;
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(state.0).as_secs_f64() * rate;
+            state.0 = now;
+            state.1 = (state.1 + refill).min(rate) - bytes as f64;
+            if state.1 < 0.0 {
+                Duration::from_secs_f64(-state.1 / rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+// retryable errors are worth another attempt (dropped connections, timeouts, 5xx),
+// fatal ones will fail the same way again (404, disk errors)
+#[derive(Debug)]
+pub enum DownloadError {
+    Retryable(String),
+    Fatal(String),
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::Retryable(e) => write!(f, "{}", e),
+            DownloadError::Fatal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() || e.is_decode() {
+            DownloadError::Retryable(e.to_string())
+        } else {
+            DownloadError::Fatal(e.to_string())
+        }
+    }
+}
+
+fn fatal(e: impl Display) -> DownloadError {
+    DownloadError::Fatal(e.to_string())
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn status_error(status: StatusCode) -> DownloadError {
+    let message = format!("Failed to download file: HTTP {}", status);
+    if is_retryable_status(status) {
+        DownloadError::Retryable(message)
+    } else {
+        DownloadError::Fatal(message)
+    }
+}
 
-    let host = reqwest::Url::parse(url)?.host_str().unwrap().to_string();
+// exponential backoff with up to 50% random jitter, capped at a minute
+fn retry_delay(base: Duration, attempt: u32) -> Duration {
+    let delay = base
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(Duration::from_secs(60));
+    let jitter = rand::thread_rng().gen_range(0..=delay.as_millis() as u64 / 2);
+    delay + Duration::from_millis(jitter)
+}
 
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(reqwest::header::ACCEPT_ENCODING, reqwest::header::HeaderValue::from_static("gzip, deflate, br"));
-    headers.insert(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("*/*"));
-    headers.insert(reqwest::header::CONNECTION, reqwest::header::HeaderValue::from_static("keep-alive"));
-    headers.insert(reqwest::header::USER_AGENT, reqwest::header::HeaderValue::from_static("PostmanRuntime/7.42.0"));
-    headers.insert(reqwest::header::HOST, reqwest::header::HeaderValue::from_str(&host).unwrap());
+// send the request, resuming from what is already in `part` when the server supports ranges,
+// returns the response and how many bytes of the file `part` already holds.
+// a resume sends `If-Range` with the validator of the response `part` was started from, so a file
+// that changed upstream is sent whole instead of appended to the old one. without a validator
+// there is no way to tell, so the download starts over
+// a stalled connection is caught by the client's per-read timeout, a slow but steady one is left to finish
+fn open_download(client: &Client, url: &str, part: &Path) -> Result<(Response, u64), DownloadError> {
+    // ask for the raw bytes, byte ranges of an encoded response can't be resumed
+    let request = || {
+        client
+            .get(url)
+            .header(reqwest::header::ACCEPT_ENCODING, "identity")
+    };
+    let existing = part.metadata().map(|m| m.len()).unwrap_or(0);
+    let validator = std::fs::read_to_string(get_validator_path(part)).ok().filter(|v| !v.trim().is_empty());
+    if let (true, Some(validator)) = (existing > 0, validator) {
+        let response = request()
+            .header(reqwest::header::RANGE, format!("bytes={}-", existing))
+            .header(reqwest::header::IF_RANGE, validator.trim())
+            .send()?;
+        let status = response.status();
+        if status == StatusCode::PARTIAL_CONTENT {
+            if content_range_start(&response) == Some(existing) {
+                return Ok((response, existing));
+            }
+        } else if status.is_success() {
+            // the file changed upstream or the server ignored the range, it is sending the whole file again
+            File::create(part).map_err(fatal)?;
+            save_validator(part, &response)?;
+            return Ok((response, 0));
+        } else if status != StatusCode::RANGE_NOT_SATISFIABLE {
+            // a failed request, keep what we have so the next attempt can resume
+            return Ok((response, existing));
+        }
+    }
+    // nothing to resume from, or the range was rejected: start from scratch
+    if existing > 0 {
+        std::fs::remove_file(part).map_err(fatal)?;
+    }
+    let response = request().send()?;
+    if response.status().is_success() {
+        save_validator(part, &response)?;
+    }
+    Ok((response, 0))
+}
 
-    // Create a client with an extended timeout
-    let client = ClientBuilder::new()
-        .timeout(Duration::from_secs(300))  // 5 minutes timeout
-        .connection_verbose(true)
-        .default_headers(headers)
-        .build()?;
+// a spinner showing the bytes received, for responses that don't say how big they are
+fn unknown_length_style() -> ProgressStyle {
+    ProgressStyle::default_spinner()
+        .template("[{elapsed_precise}] {spinner:.cyan} {bytes} {msg}")
+        .unwrap()
+}
 
-    let mut response = client.get(url).send()?;
+// a single attempt, leaves whatever it managed to get in the `.part` file.
+// `expected_size` (from the manifest) stands in for a missing Content-Length
+fn download_attempt(client: &Client, url: &str, dest: &Path, expected_size: Option<u64>, pb: &ProgressBar, options: &DownloadOptions) -> Result<(), DownloadError> {
+    let part = get_partial_path(dest);
+    let (mut response, mut downloaded) = open_download(client, url, &part)?;
 
     if !response.status().is_success() {
-        return Err(format!("Failed to download file: HTTP {}", response.status()).into());
+        return Err(status_error(response.status()));
     }
 
     let total_size = response
         .content_length()
-        .ok_or("Failed to get content length")?;
+        .map(|length| length + downloaded)
+        .or(expected_size);
 
-    if total_size <= 0 {
-        return Err(format!("Error Getting file infu").into());
+    if total_size == Some(0) {
+        return Err(fatal("Error Getting file infu"));
     }
 
-    //  get headers and print them
-    
-    let mut file = File::create(dest)?;
-    let mut downloaded: u64 = 0;
+    let mut file = OpenOptions::new().create(true).append(true).open(&part).map_err(fatal)?;
     let mut buffer = [0; 8192];
 
-    pb.set_length(total_size);
-    
+    match total_size {
+        Some(total_size) => pb.set_length(total_size),
+        None => {
+            pb.set_style(unknown_length_style());
+            pb.enable_steady_tick(Duration::from_millis(100));
+        }
+    }
+    pb.set_position(downloaded);
+    pb.set_message(format!("Downloading {}", dest.display()));
+    if downloaded > 0 {
+        pb.set_message(format!("Resuming {} at {} Mb", dest.display(), downloaded / 1024 / 1024));
+    }
+
     loop {
         match response.read(&mut buffer) {
             Ok(0) => break, // End of file
             Ok(n) => {
-                file.write_all(&buffer[..n])?;
+                file.write_all(&buffer[..n]).map_err(fatal)?;
                 downloaded += n as u64;
                 pb.set_position(downloaded);
+                if let Some(limiter) = &options.limiter {
+                    limiter.consume(n as u64);
+                }
+            }
+            Err(e) => return Err(DownloadError::Retryable(e.to_string())),
+        }
+    }
+    file.flush().map_err(fatal)?;
+    drop(file);
+
+    // without a length the server closing the stream is the only sign we're done,
+    // the size check in `verify_file` catches a short file when the manifest has a size
+    if let Some(total_size) = total_size {
+        if downloaded < total_size {
+            return Err(DownloadError::Retryable(format!(
+                "Download incomplete: got {} of {} bytes",
+                downloaded, total_size
+            )));
+        }
+    }
+    std::fs::rename(&part, dest).map_err(fatal)?;
+    let _ = std::fs::remove_file(get_validator_path(&part));
+    Ok(())
+}
+
+pub fn download_with_progress(url: &str, dest: &Path, expected_size: Option<u64>, pb: ProgressBar, options: &DownloadOptions) -> Result<(), Box<dyn std::error::Error>> {
+    create_dir_all(dest.parent().unwrap())?;
+    let client = http_client()?;
+
+    let mut attempt = 0;
+    loop {
+        match download_attempt(&client, url, dest, expected_size, &pb, options) {
+            Ok(()) => break,
+            Err(DownloadError::Retryable(e)) if attempt < options.retries => {
+                attempt += 1;
+                let delay = retry_delay(options.retry_delay, attempt);
+                pb.set_message(format!(
+                    "Retry {}/{} for {} in {:.1}s ({})",
+                    attempt,
+                    options.retries,
+                    dest.display(),
+                    delay.as_secs_f32(),
+                    e
+                ));
+                thread::sleep(delay);
             }
-            Err(e) => return Err(Box::new(e)),
+            Err(e) => return Err(e.into()),
         }
     }
-    
+
     // download size in mb
-    let downloaded = downloaded / 1024 / 1024;
+    let downloaded = dest.metadata()?.len() / 1024 / 1024;
     pb.finish_with_message(format!("Downloaded file of size: {:?} Mb to {:?}", downloaded, dest.display()));
     Ok(())
 }
 
-pub fn download_multiple_files(files: Vec<DownloadInfo>) -> Result<bool, Box<dyn std::error::Error>> {
+// download then verify a single file into the store, removing it again when verification fails.
+// the lock keeps other xupg processes from writing the same file at the same time
+fn download_and_verify(download_info: &DownloadInfo, pb: ProgressBar, options: &DownloadOptions) -> Result<(), String> {
+    let dest = download_info.dest.display().to_string();
+    let _lock = FileLock::acquire(&get_lock_path(&download_info.dest), || {
+        pb.set_message(format!("Waiting for another xupg process to finish {}", dest));
+    })
+    .map_err(|e| format!("{}: failed to lock {}: {}", download_info.url(), dest, e))?;
+
+    match restore_cached(download_info) {
+        Ok(true) => {
+            pb.finish_with_message(format!("Already downloaded {}", dest));
+            return write_metadata(download_info, None).map_err(|e| format!("{}: {}", download_info.url(), e));
+        }
+        Ok(false) => {}
+        Err(e) => return Err(format!("{}: {}", download_info.url(), e)),
+    }
+
+    pb.set_message(format!("Downloading {}", dest));
+    let result = download_info
+        .download_with_progress(pb.clone(), options)
+        .and_then(|_| download_info.verify().map_err(|e| e.into()))
+        .and_then(|_| {
+            let sha256 = match &download_info.sha256 {
+                Some(sha256) => sha256.to_lowercase(),
+                None => sha256_file(&download_info.dest)?,
+            };
+            add_to_store(&download_info.dest, &sha256)?;
+            write_metadata(download_info, Some(sha256))?;
+            Ok(())
+        });
+    if let Err(e) = result {
+        pb.abandon_with_message(format!("Failed {}", dest));
+        // delete the file if verification fails, an unfinished `.part` is kept to resume from
+        if download_info.dest.exists() {
+            let _ = std::fs::remove_file(&download_info.dest);
+        }
+        return Err(format!("{}: {}", download_info.url(), e));
+    }
+    Ok(())
+}
+
+// write the sidecar of a finished download, hashing it when the hash isn't known yet
+fn write_metadata(download_info: &DownloadInfo, sha256: Option<String>) -> std::io::Result<()> {
+    let Some(metadata) = &download_info.metadata else {
+        return Ok(());
+    };
+    let sha256 = match sha256.or_else(|| download_info.sha256.as_ref().map(|sha256| sha256.to_lowercase())) {
+        Some(sha256) => sha256,
+        None => sha256_file(&download_info.dest)?,
+    };
+    ArchiveMetadata { sha256, ..metadata.clone() }.write(&download_info.dest)
+}
+
+// download files using at most `options.jobs` threads, the rest wait in a queue
+pub fn download_multiple_files(files: Vec<DownloadInfo>, options: &DownloadOptions) -> Result<bool, Box<dyn std::error::Error>> {
     let mut threads = vec![];
     let errors =  Arc::new(Mutex::new(Vec::new())); 
     let pb = MultiProgress::new();
@@ -109,22 +569,27 @@ pub fn download_multiple_files(files: Vec<DownloadInfo>) -> Result<bool, Box<dyn
         .unwrap()
         .progress_chars("##-");
 
+    let workers = options.jobs.clamp(1, files.len().max(1));
+    let mut queue = VecDeque::new();
     for file in files {
         let pb = pb.add(ProgressBar::new(100));
         pb.set_style(style.clone());
-        pb.set_message(format!("Downloading {}", file.dest.display()));
+        pb.set_message(format!("Waiting {}", file.dest.display()));
+        queue.push_back((file, pb));
+    }
+    let queue = Arc::new(Mutex::new(queue));
 
-        let download_info = file.clone();
-        let pb_clone = pb.clone();
+    for _ in 0..workers {
+        let queue = Arc::clone(&queue);
+        let options = options.clone();
         let errors = Arc::clone(&errors);
-        threads.push(thread::spawn(move || {
-            if let Err(e) = download_info.download_with_progress(pb_clone) {
-                let mut errors = errors.lock().unwrap();  // Lock the mutex
-                errors.push(format!("{}: {}", download_info.url(), e));
-                // delete the file if download fails
-                if download_info.dest.exists() {
-                    std::fs::remove_file(download_info.dest).unwrap();
-                }
+        threads.push(thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop_front();
+            let Some((download_info, pb)) = next else {
+                break;
+            };
+            if let Err(e) = download_and_verify(&download_info, pb, &options) {
+                errors.lock().unwrap().push(e);
             }
         }));
     }
@@ -140,94 +605,169 @@ pub fn download_multiple_files(files: Vec<DownloadInfo>) -> Result<bool, Box<dyn
     Ok(true)
 }
 
-// list files in a directory
-pub fn list_files_in_dir(dir: &Path) -> Vec<PathBuf> {
-    let mut files = Vec::new();
-    if dir.is_dir() {
-        for entry in dir.read_dir().expect("Failed to read directory") {
-            if let Ok(entry) = entry {
-                files.push(entry.path());
+#[derive(Debug)]
+pub enum IntegrityError {
+    Io(String),
+    SizeMismatch { expected: u64, actual: u64 },
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+impl Display for IntegrityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrityError::Io(e) => write!(f, "Failed to read file: {}", e),
+            IntegrityError::SizeMismatch { expected, actual } => {
+                write!(f, "Size mismatch: expected {} bytes, got {} bytes", expected, actual)
+            }
+            IntegrityError::ChecksumMismatch { expected, actual } => {
+                write!(f, "Checksum mismatch: expected sha256 {}, got {}", expected, actual)
             }
         }
     }
-    files
 }
 
-// unzip file to a location
-pub fn unzip_file(file: &Path, dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let file = File::open(file)?;
-    let mut archive = zip::ZipArchive::new(file)?;
+impl std::error::Error for IntegrityError {}
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let outpath = dest.join(file.mangled_name());
+// hex encoded sha256 of a file
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-        if file.name().ends_with('/') {
-            std::fs::create_dir_all(&outpath)?;
-        } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    std::fs::create_dir_all(&p)?;
-                }
-            }
-            let mut outfile = std::fs::File::create(&outpath)?;
-            std::io::copy(&mut file, &mut outfile)?;
-
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode))?;
-                }
-            }
+// check a file against the size and sha256 from the manifest, missing values are not checked
+pub fn verify_file(path: &Path, sha256: Option<&str>, size: Option<u64>) -> Result<(), IntegrityError> {
+    if let Some(expected) = size {
+        let actual = path.metadata().map_err(|e| IntegrityError::Io(e.to_string()))?.len();
+        if actual != expected {
+            return Err(IntegrityError::SizeMismatch { expected, actual });
+        }
+    }
+    if let Some(expected) = sha256 {
+        let actual = sha256_file(path).map_err(|e| IntegrityError::Io(e.to_string()))?;
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(IntegrityError::ChecksumMismatch {
+                expected: expected.trim().to_lowercase(),
+                actual,
+            });
         }
     }
     Ok(())
 }
 
-// unzip with progress
-pub fn unzip_file_with_custom_progress(file: &Path, dest: &Path, pb: ProgressBar) -> Result<(), Box<dyn std::error::Error>> {
-    let file = File::open(file)?;
-    let mut archive = zip::ZipArchive::new(file)?;
-
-    pb.set_length(archive.len() as u64);
+// e.g 300 B, 512 KB, 37 MB or 1.2 GB
+pub fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    const GB: u64 = 1024 * MB;
+    match bytes {
+        0..KB => format!("{} B", bytes),
+        KB..MB => format!("{} KB", bytes / 1024),
+        MB..GB => format!("{} MB", bytes / MB),
+        _ => format!("{:.1} GB", bytes as f64 / GB as f64),
+    }
+}
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let outpath = dest.join(file.mangled_name());
+// list files in a directory
+pub fn list_files_in_dir(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        for entry in dir.read_dir().expect("Failed to read directory").flatten() {
+            files.push(entry.path());
+        }
+    }
+    files
+}
 
-        if file.name().ends_with('/') {
-            std::fs::create_dir_all(&outpath)?;
-        } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    std::fs::create_dir_all(&p)?;
-                }
-            }
-            let mut outfile = std::fs::File::create(&outpath)?;
-            // copy overriding existing files
-            std::io::copy(&mut file, &mut outfile)?;
-
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode))?;
-                }
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use super::*;
+
+    // a server answering one request with `response`, the request's headers are sent back lowercased
+    fn serve_once(response: &'static str) -> (String, mpsc::Receiver<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut headers = Vec::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                headers.push(line.trim().to_lowercase());
+                line.clear();
             }
+            let _ = (&stream).write_all(response.as_bytes());
+            let _ = sender.send(headers);
+        });
+        (format!("http://{}/file", address), receiver)
+    }
+
+    // a `.part` file holding `content`, with `validator` saved next to it when given
+    fn partial(name: &str, content: &str, validator: Option<&str>) -> PathBuf {
+        let part = std::env::temp_dir().join(format!("xupg-download-{}-{}.part", name, std::process::id()));
+        std::fs::write(&part, content).unwrap();
+        let _ = std::fs::remove_file(get_validator_path(&part));
+        if let Some(validator) = validator {
+            std::fs::write(get_validator_path(&part), validator).unwrap();
         }
-        pb.inc(1);
+        part
     }
-    pb.finish_with_message("Unzipped file");
-    Ok(())
-}
 
-pub fn unzip_file_with_progress(file: &Path, dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let pb = indicatif::ProgressBar::new(100);
-    let style = ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] {bar:40.cyan/blue} {msg}")
-        .unwrap()
-        .progress_chars("##-");
-    pb.set_style(style);
-    unzip_file_with_custom_progress(file, dest, pb)
-}
\ No newline at end of file
+    fn read_body(mut response: Response) -> String {
+        let mut body = String::new();
+        response.read_to_string(&mut body).unwrap();
+        body
+    }
+
+    fn cleanup(part: &Path) {
+        let _ = std::fs::remove_file(part);
+        let _ = std::fs::remove_file(get_validator_path(part));
+    }
+
+    #[test]
+    fn resumes_with_if_range() {
+        let (url, headers) = serve_once("HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-9/10\r\nContent-Length: 5\r\nConnection: close\r\n\r\n56789");
+        let part = partial("resume", "01234", Some("\"v1\""));
+        let (response, existing) = open_download(&Client::new(), &url, &part).unwrap();
+        assert_eq!(existing, 5);
+        assert_eq!(read_body(response), "56789");
+        let headers = headers.recv().unwrap();
+        assert!(headers.contains(&"range: bytes=5-".to_string()));
+        assert!(headers.contains(&"if-range: \"v1\"".to_string()));
+        assert_eq!(std::fs::read_to_string(&part).unwrap(), "01234");
+        cleanup(&part);
+    }
+
+    #[test]
+    fn starts_over_when_the_file_changed() {
+        let (url, _) = serve_once("HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 10\r\nConnection: close\r\n\r\nabcdefghij");
+        let part = partial("changed", "01234", Some("\"v1\""));
+        let (response, existing) = open_download(&Client::new(), &url, &part).unwrap();
+        assert_eq!(existing, 0);
+        assert_eq!(read_body(response), "abcdefghij");
+        assert_eq!(std::fs::read_to_string(&part).unwrap(), "");
+        assert_eq!(std::fs::read_to_string(get_validator_path(&part)).unwrap(), "\"v2\"");
+        cleanup(&part);
+    }
+
+    #[test]
+    fn does_not_resume_without_a_validator() {
+        let (url, headers) = serve_once("HTTP/1.1 200 OK\r\nETag: W/\"weak\"\r\nContent-Length: 10\r\nConnection: close\r\n\r\nabcdefghij");
+        let part = partial("unvalidated", "01234", None);
+        let (_, existing) = open_download(&Client::new(), &url, &part).unwrap();
+        assert_eq!(existing, 0);
+        assert!(!part.exists());
+        assert!(!headers.recv().unwrap().iter().any(|header| header.starts_with("range:")));
+        // a weak ETag can't be used with If-Range
+        assert!(!get_validator_path(&part).exists());
+        cleanup(&part);
+    }
+}
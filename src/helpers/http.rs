@@ -0,0 +1,64 @@
+use std::{fs, sync::OnceLock, time::Duration};
+
+use reqwest::{
+    blocking::{Client, ClientBuilder},
+    header::{HeaderMap, HeaderValue, ACCEPT, USER_AGENT},
+    Certificate, NoProxy, Proxy,
+};
+
+use super::config::{get_config, Config};
+
+// connecting shouldn't take as long as a download may stall
+const CONNECT_TIMEOUT: u64 = 30;
+
+pub fn user_agent() -> String {
+    format!("xupg/{}", env!("CARGO_PKG_VERSION"))
+}
+
+// HTTP(S)_PROXY and NO_PROXY from the environment are picked up by reqwest itself,
+// a `proxy` in the config replaces them for every request
+pub fn build_client(config: &Config) -> Result<Client, String> {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_str(&user_agent()).map_err(|e| e.to_string())?,
+    );
+
+    // the blocking client applies `timeout` to every connect, read and write on its own, not to the whole request
+    let mut builder = ClientBuilder::new()
+        .timeout(Duration::from_secs(config.timeout))
+        .connect_timeout(Duration::from_secs(config.timeout.min(CONNECT_TIMEOUT)))
+        .default_headers(headers);
+
+    if let Some(proxy_url) = &config.proxy {
+        let no_proxy = match &config.no_proxy {
+            Some(list) => NoProxy::from_string(list),
+            None => NoProxy::from_env(),
+        };
+        let proxy = Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy {}: {}", proxy_url, e))?
+            .no_proxy(no_proxy);
+        builder = builder.proxy(proxy);
+    }
+
+    for path in &config.ca_certs {
+        let pem = fs::read(path).map_err(|e| format!("Failed to read CA certificate {}: {}", path, e))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA certificate {}: {}", path, e))?;
+        if certs.is_empty() {
+            return Err(format!("No certificates found in {}", path));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+// the client every request goes through, built once from the config
+pub fn http_client() -> Result<Client, String> {
+    static CLIENT: OnceLock<Result<Client, String>> = OnceLock::new();
+    CLIENT.get_or_init(|| build_client(get_config())).clone()
+}
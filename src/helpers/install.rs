@@ -0,0 +1,222 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{api::now_secs, config::get_config, registry::list_tree};
+
+// a previous version of an install path, kept as `<path>.xupg-backup-<unix time>` next to it
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub taken_at: u64,
+}
+
+// resolve "." and trailing slashes so the target has a name to put things next to
+fn resolve_target(target: &Path) -> Result<PathBuf, String> {
+    match target.canonicalize() {
+        Ok(target) => Ok(target),
+        Err(_) => std::path::absolute(target).map_err(|e| format!("Invalid install path {}: {}", target.display(), e)),
+    }
+}
+
+fn target_name(target: &Path) -> Result<String, String> {
+    target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Can't install into {}", target.display()))
+}
+
+// staging and backup directories sit next to the target so every rename stays on one filesystem and is atomic
+fn sibling_path(target: &Path, kind: &str, stamp: u64) -> Result<PathBuf, String> {
+    Ok(target.with_file_name(format!("{}.xupg-{}-{}", target_name(target)?, kind, stamp)))
+}
+
+// a stamp no staging or backup directory of `target` uses yet
+fn next_stamp(target: &Path) -> Result<u64, String> {
+    let mut stamp = now_secs();
+    while sibling_path(target, "staging", stamp)?.exists() || sibling_path(target, "backup", stamp)?.exists() {
+        stamp += 1;
+    }
+    Ok(stamp)
+}
+
+fn remove_path(path: &Path) {
+    if path.is_dir() {
+        let _ = fs::remove_dir_all(path);
+    } else if path.exists() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// move `replacement` to `target`, the current contents of `target` are moved to `backup` first.
+// returns whether there was anything to back up, on failure `target` is left as it was
+fn swap_in(target: &Path, replacement: &Path, backup: &Path) -> Result<bool, String> {
+    let had_target = target.exists();
+    if had_target {
+        fs::rename(target, backup).map_err(|e| format!("Failed to move {} aside (is it in use?): {}", target.display(), e))?;
+    }
+    if let Err(e) = fs::rename(replacement, target) {
+        // put the previous contents back
+        if had_target {
+            if let Err(restore) = fs::rename(backup, target) {
+                return Err(format!(
+                    "Failed to swap in {}: {}. Restoring the backup also failed ({}), previous files are in {}",
+                    replacement.display(),
+                    e,
+                    restore,
+                    backup.display()
+                ));
+            }
+        }
+        return Err(format!("Failed to swap in {}: {}", replacement.display(), e));
+    }
+    Ok(had_target)
+}
+
+// snapshots of `target`, newest first
+pub fn list_snapshots(target: &Path) -> Result<Vec<Snapshot>, String> {
+    let target = resolve_target(target)?;
+    let prefix = format!("{}.xupg-backup-", target_name(&target)?);
+    let Some(parent) = target.parent() else {
+        return Ok(Vec::new());
+    };
+    let mut snapshots: Vec<Snapshot> = match fs::read_dir(parent) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let taken_at = name.strip_prefix(&prefix)?.parse::<u64>().ok()?;
+                Some(Snapshot { path: entry.path(), taken_at })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.taken_at));
+    Ok(snapshots)
+}
+
+// delete the oldest snapshots of `target` so only `keep` are left, returns the ones removed
+pub fn prune_snapshots(target: &Path, keep: usize) -> Result<Vec<PathBuf>, String> {
+    let mut removed = Vec::new();
+    for snapshot in list_snapshots(target)?.into_iter().skip(keep) {
+        fs::remove_dir_all(&snapshot.path).map_err(|e| format!("Failed to remove snapshot {}: {}", snapshot.path.display(), e))?;
+        removed.push(snapshot.path);
+    }
+    Ok(removed)
+}
+
+// apply the `snapshots` retention setting, a backup that got pruned right away is no longer returned
+fn keep_backup(target: &Path, backup: PathBuf) -> Option<PathBuf> {
+    if let Err(e) = prune_snapshots(target, get_config().snapshots) {
+        eprintln!("⚠️  {}", e);
+    }
+    backup.exists().then_some(backup)
+}
+
+fn copy_entry(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    #[cfg(unix)]
+    if from.symlink_metadata()?.file_type().is_symlink() {
+        return std::os::unix::fs::symlink(fs::read_link(from)?, to);
+    }
+    fs::copy(from, to).map(|_| ())
+}
+
+// copy the files in `target` that `placed` (what the previous install put there) doesn't list
+// into `staging`, so config files and other additions survive a reinstall. without a record of
+// the previous install every file is kept. files the new version ships win, the copied ones are returned
+pub fn carry_over(target: &Path, staging: &Path, placed: Option<&[String]>) -> Result<Vec<String>, String> {
+    let placed: Option<HashSet<&String>> = placed.map(|placed| placed.iter().collect());
+    let mut carried = Vec::new();
+    for file in list_tree(target) {
+        if placed.as_ref().is_some_and(|placed| placed.contains(&file)) || staging.join(&file).symlink_metadata().is_ok() {
+            continue;
+        }
+        copy_entry(&target.join(&file), &staging.join(&file))
+            .map_err(|e| format!("Failed to keep {}: {}", target.join(&file).display(), e))?;
+        carried.push(file);
+    }
+    Ok(carried)
+}
+
+// fill a staging directory with `fill` and swap it in place of `target` once it succeeds.
+// the previous contents are kept as a timestamped snapshot (returned), any failure leaves `target` as it was
+pub fn install_atomically(target: &Path, fill: impl FnOnce(&Path) -> Result<(), String>) -> Result<Option<PathBuf>, String> {
+    let target = resolve_target(target)?;
+    let stamp = next_stamp(&target)?;
+    let staging = sibling_path(&target, "staging", stamp)?;
+    let backup = sibling_path(&target, "backup", stamp)?;
+
+    fs::create_dir_all(&staging).map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+    let result = fill(&staging).and_then(|_| swap_in(&target, &staging, &backup));
+    match result {
+        Ok(true) => Ok(keep_backup(&target, backup)),
+        Ok(false) => Ok(None),
+        Err(e) => {
+            remove_path(&staging);
+            Err(e)
+        }
+    }
+}
+
+// put a snapshot back in place of `target`, what is there now becomes a snapshot itself so the rollback can be undone
+pub fn restore_snapshot(target: &Path, snapshot: &Path) -> Result<Option<PathBuf>, String> {
+    let target = resolve_target(target)?;
+    let backup = sibling_path(&target, "backup", next_stamp(&target)?)?;
+    match swap_in(&target, snapshot, &backup)? {
+        true => Ok(keep_backup(&target, backup)),
+        false => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xupg-install-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn carry_over_keeps_files_the_previous_install_did_not_place() {
+        let dir = temp_dir("carry");
+        let (target, staging) = (dir.join("target"), dir.join("staging"));
+        fs::create_dir_all(target.join("ext")).unwrap();
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(target.join("php.exe"), "old").unwrap();
+        fs::write(target.join("ext/old.dll"), "old").unwrap();
+        fs::write(target.join("php.ini"), "custom").unwrap();
+        fs::write(target.join("ext/custom.dll"), "custom").unwrap();
+        fs::write(staging.join("php.exe"), "new").unwrap();
+
+        let placed = vec!["php.exe".to_string(), "ext/old.dll".to_string()];
+        let mut carried = carry_over(&target, &staging, Some(&placed)).unwrap();
+        carried.sort();
+        assert_eq!(carried, vec!["ext/custom.dll", "php.ini"]);
+        assert_eq!(fs::read_to_string(staging.join("php.exe")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(staging.join("php.ini")).unwrap(), "custom");
+        assert!(!staging.join("ext/old.dll").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn carry_over_keeps_everything_without_a_record() {
+        let dir = temp_dir("carry-all");
+        let (target, staging) = (dir.join("target"), dir.join("staging"));
+        fs::create_dir_all(&target).unwrap();
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(target.join("php.exe"), "old").unwrap();
+        fs::write(target.join("php.ini"), "custom").unwrap();
+        fs::write(staging.join("php.exe"), "new").unwrap();
+
+        assert_eq!(carry_over(&target, &staging, None).unwrap(), vec!["php.ini"]);
+        assert_eq!(fs::read_to_string(staging.join("php.exe")).unwrap(), "new");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
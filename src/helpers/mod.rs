@@ -1,9 +1,16 @@
 use colored::*;
-use std::fmt::Display;
+use std::{fmt::Display, io::Write};
 
 pub mod api;
+pub mod archive;
+pub mod config;
 pub mod file;
+pub mod http;
+pub mod install;
 pub mod package;
+pub mod registry;
+pub mod store;
+pub mod version;
 
 //  a function to return the platform type , either windows , linux or mac if non return None
 pub fn get_platform_os() -> Option<String> {
@@ -19,6 +26,20 @@ pub fn get_platform_os() -> Option<String> {
 
 
 
+// ask a yes/no question on the terminal, `yes` (from --yes) answers it without asking
+pub fn confirm(question: &str, yes: bool) -> bool {
+    if yes {
+        return true;
+    }
+    print!("{} {} ", question, "[y/N]".dimmed());
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
 pub fn print_table<T, const N: usize>(header: [String; N], data: &[[T; N]])
 where
     T: Display,
@@ -6,74 +6,36 @@ use std::{
 
 
 
-use super::file::{get_download_dir, list_files_in_dir, unzip_file, unzip_file_with_progress};
-
-pub enum SupportedPackages {
-    PHP,
-    MySQL,
-    PHPMyAdmin,
-}
-
-impl SupportedPackages {
-    pub fn get_name(&self) -> &str {
-        match self {
-            SupportedPackages::PHP => "PHP",
-            SupportedPackages::MySQL => "MySQL",
-            SupportedPackages::PHPMyAdmin => "PHPMyAdmin"
-        }
-    }
-
-    pub fn get_local_versions(&self) -> Vec<Version> {
-    let php_zips = list_files_in_dir(&get_download_dir(self.get_name().to_lowercase().as_str()));
-    let mut table_data = Vec::new();
-    for php_zip in php_zips {
-        let extension = php_zip.extension().unwrap().to_str().unwrap();
-        let file_name = php_zip.file_name().unwrap().to_str().unwrap();
-        let version = file_name.split("-").nth(1).unwrap();
-        // remove the extension
-        let version = version.replace(&format!(".{}", extension), "");
-        // table_data.push(vec![version.to_string(), php_zip.display().to_string(), format!("{} MB", file_size)]);
-        //from path buf to path
-        table_data.push(Version::new_local(
-            "PHP".to_string(),
-            version.to_string(),
-            php_zip,
-        ));
-    }
-    table_data
-}
-
-
-    //  return a vector of supported packages
-    pub fn iter() -> Vec<SupportedPackages> {
-        vec![
-            SupportedPackages::PHP,
-            SupportedPackages::MySQL,
-            SupportedPackages::PHPMyAdmin,
-        ]
-    }
-}
+use colored::Colorize;
+use indicatif::ProgressBar;
+
+use crate::providers::PackageProvider;
+
+use super::{
+    archive::{extract, extract_with_progress, ExtractOptions},
+    api::now_secs,
+    file::{format_size, sha256_file, ArchiveMetadata},
+    install::{carry_over, install_atomically},
+    registry::{list_tree, normalize_path, Installation, Registry},
+    version::{SemVer, VersionReq},
+};
 
 pub enum AppInstallError {
     PathDoesNotExist(String),
     VersionNotAvailable(String),
-    UnAvailableOffline,
-    InstallFailed,
+    InstallFailed(String),
 }
 
 impl Display for AppInstallError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AppInstallError::PathDoesNotExist(path) => {
-                write!(f, "{}", format!("Path {} does not exist", path))
+                write!(f, "Path {} does not exist", path)
             }
             AppInstallError::VersionNotAvailable(version) => {
-                write!(f, "{}", format!("Version {} is not available", version))
-            }
-            AppInstallError::UnAvailableOffline => {
-                write!(f, "{}", "Version is not available offline")
+                write!(f, "Version {} is not available", version)
             }
-            AppInstallError::InstallFailed => write!(f, "{}", "Installation failed"),
+            AppInstallError::InstallFailed(reason) => write!(f, "Installation failed: {}", reason),
         }
     }
 }
@@ -82,33 +44,32 @@ impl Display for AppInstallError {
 
 
 pub struct Package {
-    pub name: SupportedPackages,
-    pub versions: HashMap<String, Version>,
+    pub provider: &'static dyn PackageProvider,
+    pub versions: HashMap<SemVer, Version>,
 }
 
+#[derive(Clone)]
 pub struct Version {
     name: String,
-    version: String,
+    version: SemVer,
     location: String,
     size: String,
-    offline: bool,
 }
 
 impl Package {
-    pub fn new(name: SupportedPackages) -> Self {
+    pub fn new(provider: &'static dyn PackageProvider) -> Self {
         Package {
-            name,
+            provider,
             versions: HashMap::new(),
         }
     }
 
     pub fn get_name(&self) -> &str {
-        &self.name.get_name()
+        self.provider.display_name()
     }
 
     pub fn add_version(&mut self, version: Version) {
-        self.versions
-            .insert(version.get_version().to_string(), version);
+        self.versions.insert(version.get_semver().clone(), version);
     }
 
     pub fn add_versions(&mut self, versions: Vec<Version>) {
@@ -118,54 +79,40 @@ impl Package {
     }
 
     pub fn load_local_versions(&mut self) {
-        let versions = self.name.get_local_versions();
+        let versions = self.provider.get_local_versions();
         self.add_versions(versions);
     }
 
+    // looked up as a version, so "8.1" also finds "8.1.0"
     pub fn get_version(&self, version: &str) -> Option<&Version> {
-        self.versions.get(version)
-    }
-
-    pub fn has_version(&self, version: &str) -> bool {
-        self.versions.contains_key(version)
+        self.versions.get(&version.parse::<SemVer>().ok()?)
     }
 
-    pub fn remove_version(&mut self, version: &str) {
-        self.versions.remove(version);
+    // the newest downloaded version satisfying a constraint, `lts` lists the versions marked LTS in the manifest.
+    // like the manifest, a download also matches by the exact release its sidecar names, e.g 8.1.26 for 8.1
+    pub fn resolve_version(&self, requirement: &VersionReq, lts: &[SemVer]) -> Option<&Version> {
+        let candidates = self.versions.iter().map(|(semver, version)| {
+            let matched = match version.get_release_version().parse::<SemVer>().ok() {
+                Some(exact) if requirement.matches(&exact) => exact,
+                _ => semver.clone(),
+            };
+            (matched, lts.contains(semver), version)
+        });
+        requirement.resolve(candidates).map(|(_, version)| version)
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<String, Version> {
-        self.versions.iter()
-    }
-
-    pub fn vec(&self) -> Vec<&Version> {
-        self.versions.values().collect()
+    pub fn has_version(&self, version: &str) -> bool {
+        self.get_version(version).is_some()
     }
 }
 
 impl Version {
-    pub fn new_local(name: String, version: String, file: PathBuf) -> Self {
+    pub fn new_local(name: String, version: SemVer, file: PathBuf) -> Self {
         Version {
             name,
             version,
             location: file.to_string_lossy().to_string(),
-            size: format!("{} MB", file.metadata().unwrap().len() / 1024 / 1024),
-            offline: true,
-        }
-    }
-
-    pub fn new_online(
-        name: String,
-        version: String,
-        location: String,
-        size: Option<String>,
-    ) -> Self {
-        Version {
-            name,
-            version,
-            location,
-            size: size.unwrap_or_else(|| "Unknown".to_string()),
-            offline: false,
+            size: format_size(file.metadata().unwrap().len()),
         }
     }
 }
@@ -176,9 +123,20 @@ impl Version {
     }
 
     pub fn get_version(&self) -> &str {
+        self.version.as_str()
+    }
+
+    pub fn get_semver(&self) -> &SemVer {
         &self.version
     }
 
+    // the exact release the download is according to its sidecar, e.g "8.1.26" for "8.1"
+    pub fn get_release_version(&self) -> String {
+        ArchiveMetadata::read(Path::new(&self.location))
+            .and_then(|metadata| metadata.release_version)
+            .unwrap_or_else(|| self.get_version().to_string())
+    }
+
     pub fn get_location(&self) -> &str {
         &self.location
     }
@@ -186,10 +144,6 @@ impl Version {
     pub fn get_size(&self) -> &str {
         &self.size
     }
-
-    pub fn is_offline(&self) -> bool {
-        self.offline
-    }
 }
 
 impl Package {
@@ -197,32 +151,63 @@ impl Package {
         &self,
         version: &str,
         target_path: &str,
+        options: &ExtractOptions,
         with_pb: bool,
-    ) -> Result<(), AppInstallError> {
+    ) -> Result<Option<PathBuf>, AppInstallError> {
         // check if version is available
-        if !self.has_version(&version) {
+        if !self.has_version(version) {
             return Err(AppInstallError::VersionNotAvailable(version.to_string()));
         }
         // check if path exists
-        let install_path = Path::new(&target_path);
+        let install_path = Path::new(target_path);
         if !install_path.exists() {
             return Err(AppInstallError::PathDoesNotExist(target_path.to_string()));
         }
-        let version_info = self.get_version(&version).unwrap();
-        if !version_info.is_offline() {
-            return Err(AppInstallError::UnAvailableOffline);
-        }
+        let version_info = self.get_version(version).unwrap();
+        // the package's own default applies when --strip-components isn't given
+        let options = &options
+            .clone()
+            .with_strip_components(options.strip_components.or(self.provider.strip_components()));
         let file: &str = version_info.get_location();
         let file = Path::new(file);
-        if with_pb {
-            if let Err(_) = unzip_file_with_progress(file, install_path) {
-                return Err(AppInstallError::InstallFailed);
-            }
-        } else {
-            if let Err(_) = unzip_file(file, install_path) {
-                return Err(AppInstallError::InstallFailed);
-            }
+        let archive_sha256 = sha256_file(file).map_err(|e| AppInstallError::InstallFailed(e.to_string()))?;
+        // what the previous install placed, everything else at the path belongs to the user
+        let placed = Registry::load()
+            .ok()
+            .and_then(|registry| registry.find(install_path).map(|previous| previous.files.clone()));
+        let mut files = Vec::new();
+        // extract next to the target and only swap it in once everything succeeded
+        let backup = install_atomically(install_path, |staging| {
+            let result = if with_pb {
+                extract_with_progress(file, staging, options)
+            } else {
+                extract(file, staging, options, &ProgressBar::hidden())
+            };
+            result.map_err(|e| e.to_string())?;
+            // only what the archive holds is recorded, files carried over or made by post_install
+            // (like a php.ini the user goes on to edit) are left alone by uninstall
+            files = list_tree(staging);
+            carry_over(install_path, staging, placed.as_deref())?;
+            self.provider.post_install(staging)?;
+            Ok(())
+        })
+        .map_err(AppInstallError::InstallFailed)?;
+
+        let metadata = ArchiveMetadata::read(file);
+        let installation = Installation {
+            package: self.provider.name().to_string(),
+            version: version_info.get_version().to_string(),
+            archive_sha256,
+            path: normalize_path(install_path),
+            installed_at: now_secs(),
+            strip_components: options.strip_components,
+            files,
+            url: metadata.as_ref().map(|metadata| metadata.url.clone()),
+            release_version: metadata.and_then(|metadata| metadata.release_version),
+        };
+        if let Err(e) = Registry::update(|registry| registry.record(installation, backup.as_deref())) {
+            eprintln!("⚠️  {} {}", "Installed, but failed to record it:".yellow(), e.dimmed());
         }
-        Ok(())
+        Ok(backup)
     }
 }
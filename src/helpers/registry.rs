@@ -0,0 +1,146 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    file::get_xupg_dir,
+    store::{get_lock_path, FileLock},
+};
+
+// a version of a package that xupg placed at a path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Installation {
+    // lowercase package name, e.g "php"
+    pub package: String,
+    pub version: String,
+    // sha256 of the archive it was installed from
+    pub archive_sha256: String,
+    pub path: String,
+    // unix time
+    pub installed_at: u64,
+    #[serde(default)]
+    pub strip_components: Option<usize>,
+    // files and symlinks placed by the install, relative to `path`
+    pub files: Vec<String>,
+    // where the archive came from and the exact version the manifest said it was, unknown for older records
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub release_version: Option<String>,
+}
+
+// every install xupg knows about, kept in ~/.xupg/registry.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default)]
+    pub installations: Vec<Installation>,
+    // what each snapshot directory of an install path held, keyed by the snapshot's path
+    #[serde(default)]
+    pub snapshots: BTreeMap<String, Installation>,
+}
+
+pub fn get_registry_path() -> PathBuf {
+    get_xupg_dir().join("registry.json")
+}
+
+// paths are compared fully resolved so "./php" and "/opt/lampp/php" match
+pub fn normalize_path(path: &Path) -> String {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+// every file and symlink below `dir`, relative to it
+pub fn list_tree(dir: &Path) -> Vec<String> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => walk(root, &path, files),
+                Ok(_) => {
+                    if let Ok(relative) = path.strip_prefix(root) {
+                        files.push(relative.to_string_lossy().to_string());
+                    }
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, dir, &mut files);
+    files.sort();
+    files
+}
+
+impl Registry {
+    pub fn load() -> Result<Self, String> {
+        let path = get_registry_path();
+        if !path.exists() {
+            return Ok(Registry::default());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid install registry {}: {}", path.display(), e))
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        // snapshots removed by the retention policy or by hand are forgotten
+        self.snapshots.retain(|path, _| Path::new(path).exists());
+        let path = get_registry_path();
+        let tmp = path.with_extension("json.tmp");
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::create_dir_all(get_xupg_dir()).map_err(|e| e.to_string())?;
+        fs::write(&tmp, content).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    // load, change and save the registry while holding its lock so concurrent runs don't lose entries
+    pub fn update<T>(change: impl FnOnce(&mut Registry) -> T) -> Result<T, String> {
+        let _lock = FileLock::acquire(&get_lock_path(&get_registry_path()), || {}).map_err(|e| e.to_string())?;
+        let mut registry = Registry::load()?;
+        let result = change(&mut registry);
+        registry.save()?;
+        Ok(result)
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&Installation> {
+        let path = normalize_path(path);
+        self.installations.iter().find(|i| i.path == path)
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<Installation> {
+        let path = normalize_path(path);
+        let index = self.installations.iter().position(|i| i.path == path)?;
+        Some(self.installations.remove(index))
+    }
+
+    // record a new install, whatever was recorded for its path before now lives in `backup` (if kept)
+    pub fn record(&mut self, installation: Installation, backup: Option<&Path>) {
+        let previous = self.remove(Path::new(&installation.path));
+        if let (Some(previous), Some(backup)) = (previous, backup) {
+            self.snapshots.insert(normalize_path(backup), previous);
+        }
+        self.installations.push(installation);
+    }
+
+    // `snapshot` was put back at `path` and the contents it replaced moved to `backup`
+    pub fn record_rollback(&mut self, path: &Path, snapshot: &Path, backup: Option<&Path>) {
+        let restored = self.snapshots.remove(&normalize_path(snapshot));
+        let current = self.remove(path);
+        if let (Some(current), Some(backup)) = (current, backup) {
+            self.snapshots.insert(normalize_path(backup), current);
+        }
+        if let Some(mut restored) = restored {
+            restored.path = normalize_path(path);
+            self.installations.push(restored);
+        }
+    }
+}
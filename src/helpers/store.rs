@@ -0,0 +1,123 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use fs4::FileExt;
+
+use super::file::{get_partial_path, get_xupg_dir, sha256_file, verify_file, ArchiveMetadata, DownloadInfo};
+
+// downloads are kept once per content hash under store/sha256/<hash>,
+// the files in module/downloads are hard links (or copies) of them
+pub fn get_store_dir() -> PathBuf {
+    let mut path = get_xupg_dir();
+    path.push("module/store/sha256");
+    path
+}
+
+pub fn get_store_path(sha256: &str) -> PathBuf {
+    get_store_dir().join(sha256.trim().to_lowercase())
+}
+
+pub fn get_lock_path(dest: &Path) -> PathBuf {
+    let file_name = dest.file_name().unwrap_or_default().to_string_lossy();
+    get_xupg_dir().join("locks").join(format!("{}.lock", file_name))
+}
+
+// an exclusive lock held on a file in ~/.xupg/locks, released on drop (or when the process dies)
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    // take the lock, calling `on_wait` once if another process is holding it
+    pub fn acquire(path: &Path, on_wait: impl FnOnce()) -> io::Result<FileLock> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        if file.try_lock_exclusive().is_err() {
+            on_wait();
+            file.lock_exclusive()?;
+        }
+        Ok(FileLock { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+// point `dest` at the stored copy, replacing whatever is there in one rename
+pub fn link_from_store(sha256: &str, dest: &Path) -> io::Result<()> {
+    let stored = get_store_path(sha256);
+    let tmp = get_partial_path(dest);
+    if tmp.exists() {
+        fs::remove_file(&tmp)?;
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::hard_link(&stored, &tmp).is_err() {
+        fs::copy(&stored, &tmp)?;
+    }
+    fs::rename(&tmp, dest)?;
+    // renaming onto another link of the same file is a no-op that leaves `tmp` behind
+    if tmp.exists() {
+        fs::remove_file(&tmp)?;
+    }
+    Ok(())
+}
+
+// move a completed, verified download into the store and link it back into place
+pub fn add_to_store(file: &Path, sha256: &str) -> io::Result<PathBuf> {
+    let stored = get_store_path(sha256);
+    if !stored.exists() {
+        fs::create_dir_all(get_store_dir())?;
+        fs::rename(file, &stored)?;
+    }
+    link_from_store(sha256, file)?;
+    Ok(stored)
+}
+
+// the file at `dest` was downloaded from another url or is another release than the one asked for,
+// e.g the manifest moved "8.1" to a newer build. only the sidecar can tell when there's no checksum
+fn is_other_build(info: &DownloadInfo) -> bool {
+    let (Some(wanted), Some(found)) = (&info.metadata, ArchiveMetadata::read(&info.dest)) else {
+        return false;
+    };
+    !found.is_build(&wanted.url, wanted.release_version.as_deref())
+}
+
+// reuse a verified copy of the download if there is one, either in the store
+// (same content under another name) or already at the destination
+pub fn restore_cached(info: &DownloadInfo) -> io::Result<bool> {
+    if let Some(sha256) = &info.sha256 {
+        let stored = get_store_path(sha256);
+        if stored.exists() && verify_file(&stored, Some(sha256), info.size).is_ok() {
+            link_from_store(sha256, &info.dest)?;
+            return Ok(true);
+        }
+    }
+    if !info.dest.exists() {
+        return Ok(false);
+    }
+    if info.verify().is_err() || is_other_build(info) {
+        // a bad or outdated copy, get rid of it so it is downloaded again
+        fs::remove_file(&info.dest)?;
+        return Ok(false);
+    }
+    let sha256 = match &info.sha256 {
+        Some(sha256) => sha256.to_lowercase(),
+        None => sha256_file(&info.dest)?,
+    };
+    add_to_store(&info.dest, &sha256)?;
+    Ok(true)
+}
@@ -0,0 +1,529 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PreReleaseKind {
+    Alpha,
+    Beta,
+    RC,
+}
+
+// a version like 8.1, 8.3.12 or 8.4.0RC1, missing parts count as 0 so "8.1" == "8.1.0".
+// compared as major, minor, patch, then pre-releases (alpha < beta < RC) before the release itself
+#[derive(Debug, Clone)]
+pub struct SemVer {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<(PreReleaseKind, u64)>,
+    // how many of major.minor.patch were written, e.g 2 for "8.1"
+    pub parts: usize,
+    // the version as it was written, shown back to the user
+    text: String,
+}
+
+impl SemVer {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        SemVer {
+            major,
+            minor,
+            patch,
+            pre: None,
+            parts: 3,
+            text: format!("{}.{}.{}", major, minor, patch),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    fn key(&self) -> (u64, u64, u64, Option<(PreReleaseKind, u64)>) {
+        (self.major, self.minor, self.patch, self.pre)
+    }
+}
+
+impl FromStr for SemVer {
+    type Err = String;
+
+    // accepts an optional leading "v" and pre-release tags like RC1, -rc.1, alpha2 or beta
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid version {}", text);
+        let version = text.trim();
+        let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+        let split = version.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(version.len());
+        let (numbers, tag) = version.split_at(split);
+
+        let numbers: Vec<u64> = numbers
+            .trim_end_matches('.')
+            .split('.')
+            .map(|part| part.parse::<u64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        if numbers.is_empty() || numbers.len() > 3 {
+            return Err(invalid());
+        }
+
+        let tag = tag.trim_start_matches(['-', '.', '_']).to_lowercase();
+        let pre = if tag.is_empty() {
+            None
+        } else {
+            let (kind, number) = [("alpha", PreReleaseKind::Alpha), ("beta", PreReleaseKind::Beta), ("rc", PreReleaseKind::RC)]
+                .into_iter()
+                .find_map(|(name, kind)| tag.strip_prefix(name).map(|number| (kind, number)))
+                .ok_or_else(invalid)?;
+            let number = number.trim_start_matches(['-', '.', '_']);
+            let number = match number {
+                "" => 0,
+                number => number.parse::<u64>().map_err(|_| invalid())?,
+            };
+            Some((kind, number))
+        };
+
+        Ok(SemVer {
+            major: numbers[0],
+            minor: numbers.get(1).copied().unwrap_or(0),
+            patch: numbers.get(2).copied().unwrap_or(0),
+            pre,
+            parts: numbers.len(),
+            text: text.trim().to_string(),
+        })
+    }
+}
+
+impl Display for SemVer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl PartialEq for SemVer {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SemVer {}
+
+impl Hash for SemVer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl Ord for SemVer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            // a release sorts after its pre-releases
+            .then_with(|| match (self.pre, other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(&b),
+            })
+    }
+}
+
+impl PartialOrd for SemVer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// newest first, versions that don't parse go last in their original order
+pub fn sort_versions_desc<T>(items: &mut [T], version: impl Fn(&T) -> &str) {
+    items.sort_by(|a, b| match (version(a).parse::<SemVer>(), version(b).parse::<SemVer>()) {
+        (Ok(a), Ok(b)) => b.cmp(&a),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => Ordering::Equal,
+    });
+}
+
+// compare two version strings as versions, falling back to plain text when either doesn't parse
+pub fn same_version(a: &str, b: &str) -> bool {
+    match (a.parse::<SemVer>(), b.parse::<SemVer>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.trim() == b.trim(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+// what a version has to satisfy, e.g "8.2", "^8.1", "~8.1", ">=8.0 <8.3", "latest" or "lts"
+#[derive(Debug, Clone)]
+pub enum VersionReq {
+    // the newest release
+    Latest,
+    // the newest release marked as long term support
+    Lts,
+    // every comparator has to match
+    Range(Vec<(Op, SemVer)>),
+}
+
+// the first version after every version starting with the written parts, e.g 8.2 -> 8.3.0
+fn next_after(version: &SemVer) -> SemVer {
+    match version.parts {
+        1 => SemVer::new(version.major + 1, 0, 0),
+        2 => SemVer::new(version.major, version.minor + 1, 0),
+        _ => SemVer::new(version.major, version.minor, version.patch + 1),
+    }
+}
+
+fn base(version: &SemVer) -> SemVer {
+    SemVer::new(version.major, version.minor, version.patch)
+}
+
+// "8.2" is every 8.2.x, "8.2.3" is only 8.2.3
+fn partial_range(version: SemVer) -> Vec<(Op, SemVer)> {
+    if version.parts == 3 || version.pre.is_some() {
+        return vec![(Op::Eq, version)];
+    }
+    vec![(Op::Ge, base(&version)), (Op::Lt, next_after(&version))]
+}
+
+fn parse_comparator(token: &str) -> Result<Vec<(Op, SemVer)>, String> {
+    let (op, version) = [">=", "<=", ">", "<", "=", "^", "~"]
+        .into_iter()
+        .find_map(|op| token.strip_prefix(op).map(|version| (op, version)))
+        .unwrap_or(("", token));
+    let version: SemVer = version.parse()?;
+    let partial = version.parts < 3 && version.pre.is_none();
+    Ok(match op {
+        "" | "=" => partial_range(version),
+        ">=" => vec![(Op::Ge, version)],
+        "<" => vec![(Op::Lt, version)],
+        // "<=8.2" still allows every 8.2.x, ">8.2" starts after them
+        "<=" if partial => vec![(Op::Lt, next_after(&version))],
+        "<=" => vec![(Op::Le, version)],
+        ">" if partial => vec![(Op::Ge, next_after(&version))],
+        ">" => vec![(Op::Gt, version)],
+        "~" => {
+            let upper = match version.parts {
+                1 => SemVer::new(version.major + 1, 0, 0),
+                _ => SemVer::new(version.major, version.minor + 1, 0),
+            };
+            vec![(Op::Ge, version), (Op::Lt, upper)]
+        }
+        // "^" allows changes that don't touch the left-most non-zero part
+        _ => {
+            let upper = if version.major > 0 || version.parts == 1 {
+                SemVer::new(version.major + 1, 0, 0)
+            } else if version.minor > 0 || version.parts == 2 {
+                SemVer::new(0, version.minor + 1, 0)
+            } else {
+                SemVer::new(0, 0, version.patch + 1)
+            };
+            vec![(Op::Ge, version), (Op::Lt, upper)]
+        }
+    })
+}
+
+impl FromStr for VersionReq {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let requirement = text.trim().to_lowercase();
+        match requirement.as_str() {
+            "latest" | "*" | "" => return Ok(VersionReq::Latest),
+            "lts" => return Ok(VersionReq::Lts),
+            _ => {}
+        }
+        // ">= 8.0" is the same as ">=8.0"
+        let mut tokens: Vec<String> = Vec::new();
+        for token in requirement.split([' ', ',']).filter(|t| !t.is_empty()) {
+            match tokens.last_mut() {
+                Some(last) if last.chars().all(|c| "<>=^~".contains(c)) => last.push_str(token),
+                _ => tokens.push(token.to_string()),
+            }
+        }
+        let mut comparators = Vec::new();
+        for token in tokens {
+            comparators.extend(parse_comparator(&token).map_err(|_| format!("Invalid version constraint {}", text.trim()))?);
+        }
+        Ok(VersionReq::Range(comparators))
+    }
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &SemVer) -> bool {
+        match self {
+            VersionReq::Latest | VersionReq::Lts => version.pre.is_none(),
+            VersionReq::Range(comparators) => {
+                // pre-releases only match constraints that name one
+                if version.pre.is_some() && !comparators.iter().any(|(_, v)| v.pre.is_some()) {
+                    return false;
+                }
+                comparators.iter().all(|(op, wanted)| match op {
+                    Op::Eq => version == wanted,
+                    Op::Gt => version > wanted,
+                    Op::Ge => version >= wanted,
+                    Op::Lt => version < wanted,
+                    Op::Le => version <= wanted,
+                })
+            }
+        }
+    }
+
+    // the newest candidate that satisfies the requirement, candidates are (version, is lts, value)
+    pub fn resolve<T>(&self, candidates: impl IntoIterator<Item = (SemVer, bool, T)>) -> Option<(SemVer, T)> {
+        candidates
+            .into_iter()
+            .filter(|(version, lts, _)| self.matches(version) && (*lts || !matches!(self, VersionReq::Lts)))
+            .max_by(|(a, _, _), (b, _, _)| a.cmp(b))
+            .map(|(version, _, value)| (version, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn v(text: &str) -> SemVer {
+        text.parse().unwrap()
+    }
+
+    fn req(text: &str) -> VersionReq {
+        text.parse().unwrap()
+    }
+
+    fn hash(version: &SemVer) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        version.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn orders_numerically() {
+        assert!(v("8.10") > v("8.9"));
+        assert!(v("10.0") > v("7.4"));
+        assert!(v("8.1.10") > v("8.1.9"));
+        assert!(v("8") < v("8.0.1"));
+    }
+
+    #[test]
+    fn orders_pre_releases_before_the_release() {
+        let ordered = ["8.4.0alpha1", "8.4.0alpha2", "8.4.0beta1", "8.4.0RC1", "8.4.0RC2", "8.4.0", "8.4.1"];
+        for pair in ordered.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(v("8.4.0-rc.1"), v("8.4.0RC1"));
+        assert_eq!(v("8.4.0beta"), v("8.4.0beta0"));
+    }
+
+    #[test]
+    fn missing_parts_count_as_zero() {
+        assert_eq!(v("8.1"), v("8.1.0"));
+        assert_eq!(v("8"), v("8.0.0"));
+        assert_eq!(hash(&v("8.1")), hash(&v("8.1.0")));
+        assert_ne!(v("8.1"), v("8.1.1"));
+        assert!(same_version("v8.1", "8.1.0"));
+    }
+
+    #[test]
+    fn parses_and_displays_as_written() {
+        for text in ["8.1", "8.3.12", "8.4.0RC1", "v20.11.0", "5.2.1-beta2", "10"] {
+            let version = v(text);
+            assert_eq!(version.to_string(), text);
+            assert_eq!(v(&version.to_string()), version);
+        }
+        let version = v("v8.4.0-rc.2");
+        assert_eq!((version.major, version.minor, version.patch, version.pre), (8, 4, 0, Some((PreReleaseKind::RC, 2))));
+        assert_eq!(version.parts, 3);
+        assert_eq!(v("8.1").parts, 2);
+        assert_eq!(SemVer::new(8, 1, 0).to_string(), "8.1.0");
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        for text in ["", "abc", "8.1.2.3", "8.x", "8.1gamma", "8..1", "8.1RCx"] {
+            assert!(text.parse::<SemVer>().is_err(), "{:?} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn sorts_newest_first() {
+        let mut versions = vec!["7.4", "not a version", "8.10", "8.9", "8.4.0RC1", "8.4.0"];
+        sort_versions_desc(&mut versions, |version| version);
+        assert_eq!(versions, ["8.10", "8.9", "8.4.0", "8.4.0RC1", "7.4", "not a version"]);
+    }
+
+    #[test]
+    fn caret_allows_changes_left_of_the_first_non_zero_part() {
+        let caret = req("^8.1");
+        assert!(caret.matches(&v("8.1.0")));
+        assert!(caret.matches(&v("8.9.3")));
+        assert!(!caret.matches(&v("8.0.9")));
+        assert!(!caret.matches(&v("9.0.0")));
+
+        let caret = req("^0.2.3");
+        assert!(caret.matches(&v("0.2.9")));
+        assert!(!caret.matches(&v("0.3.0")));
+    }
+
+    #[test]
+    fn tilde_allows_patch_changes() {
+        let tilde = req("~8.1");
+        assert!(tilde.matches(&v("8.1.0")));
+        assert!(tilde.matches(&v("8.1.30")));
+        assert!(!tilde.matches(&v("8.2.0")));
+
+        let tilde = req("~8.1.4");
+        assert!(!tilde.matches(&v("8.1.3")));
+        assert!(tilde.matches(&v("8.1.4")));
+    }
+
+    #[test]
+    fn partial_bounds_cover_every_patch() {
+        let le = req("<=8.2");
+        assert!(le.matches(&v("8.2.99")));
+        assert!(!le.matches(&v("8.3.0")));
+
+        let gt = req(">8.2");
+        assert!(!gt.matches(&v("8.2.5")));
+        assert!(gt.matches(&v("8.3.0")));
+
+        let exact = req("8.2");
+        assert!(exact.matches(&v("8.2.7")));
+        assert!(!exact.matches(&v("8.3.0")));
+
+        let range = req(">= 8.0, <8.3");
+        assert!(range.matches(&v("8.2.9")));
+        assert!(!range.matches(&v("8.3.0")));
+        assert!(!range.matches(&v("7.4.33")));
+    }
+
+    #[test]
+    fn pre_releases_only_match_when_asked_for() {
+        assert!(!req("^8.3").matches(&v("8.4.0RC1")));
+        assert!(!req("latest").matches(&v("8.4.0RC1")));
+        assert!(!req(">=8.0").matches(&v("8.4.0beta1")));
+        assert!(req(">=8.4.0alpha1").matches(&v("8.4.0RC1")));
+        assert!(req("8.4.0RC1").matches(&v("8.4.0RC1")));
+    }
+
+    #[test]
+    fn resolves_the_newest_match() {
+        let candidates = || {
+            [("8.1.2", true), ("8.2.10", true), ("8.3.0", false), ("8.4.0RC1", false)]
+                .into_iter()
+                .map(|(version, lts)| (v(version), lts, version))
+        };
+        assert_eq!(req("latest").resolve(candidates()).map(|(_, value)| value), Some("8.3.0"));
+        assert_eq!(req("lts").resolve(candidates()).map(|(_, value)| value), Some("8.2.10"));
+        assert_eq!(req("^8.1 <8.3").resolve(candidates()).map(|(_, value)| value), Some("8.2.10"));
+        assert_eq!(req("9").resolve(candidates()).map(|(_, value)| value), None);
+    }
+
+    #[test]
+    fn rejects_invalid_constraints() {
+        assert!("^x".parse::<VersionReq>().is_err());
+        assert!(">=8.0 <".parse::<VersionReq>().is_err());
+    }
+
+    // a version written the way releases name them, e.g "8.1", "v8.3.12", "8.4.0RC1" or "8.4.0-beta.2"
+    fn version_text() -> impl Strategy<Value = String> {
+        let numbers = prop::collection::vec(0u64..20, 1..=3);
+        let tag = prop::option::of((
+            prop::sample::select(vec!["alpha", "beta", "RC", "rc"]),
+            prop::sample::select(vec!["", "-", "."]),
+            prop::option::of(0u64..5),
+        ));
+        (any::<bool>(), numbers, tag).prop_map(|(v, numbers, tag)| {
+            let numbers: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+            let tag = match tag {
+                Some((kind, separator, number)) => {
+                    format!("{}{}{}", separator, kind, number.map(|n| n.to_string()).unwrap_or_default())
+                }
+                None => String::new(),
+            };
+            format!("{}{}{}", if v { "v" } else { "" }, numbers.join("."), tag)
+        })
+    }
+
+    fn version() -> impl Strategy<Value = SemVer> {
+        version_text().prop_map(|text| v(&text))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_display_round_trips(text in version_text()) {
+            let version = v(&text);
+            prop_assert_eq!(version.to_string(), text.clone());
+            prop_assert_eq!(v(&version.to_string()), version.clone());
+            prop_assert_eq!(version.cmp(&v(&text)), Ordering::Equal);
+        }
+
+        #[test]
+        fn prop_missing_parts_are_zero(major in 0u64..50, minor in 0u64..50) {
+            let short = v(&format!("{}.{}", major, minor));
+            let long = v(&format!("{}.{}.0", major, minor));
+            prop_assert_eq!(&short, &long);
+            prop_assert_eq!(short.cmp(&long), Ordering::Equal);
+            prop_assert_eq!(hash(&short), hash(&long));
+        }
+
+        #[test]
+        fn prop_ord_agrees_with_eq_and_hash(a in version(), b in version()) {
+            prop_assert_eq!(a == b, a.cmp(&b) == Ordering::Equal);
+            prop_assert_eq!(a.partial_cmp(&b), Some(a.cmp(&b)));
+            if a == b {
+                prop_assert_eq!(hash(&a), hash(&b));
+            }
+        }
+
+        #[test]
+        fn prop_ordering_is_total_and_transitive(a in version(), b in version(), c in version()) {
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            prop_assert_eq!(a.cmp(&a), Ordering::Equal);
+            if a <= b && b <= c {
+                prop_assert!(a <= c, "{} <= {} <= {} but not {} <= {}", a, b, c, a, c);
+            }
+            if a < b && b < c {
+                prop_assert!(a < c);
+            }
+        }
+
+        #[test]
+        fn prop_pre_releases_sort_before_their_release(text in version_text()) {
+            let version = v(&text);
+            let release = SemVer::new(version.major, version.minor, version.patch);
+            match version.pre {
+                Some(_) => prop_assert!(version < release),
+                None => prop_assert_eq!(version, release),
+            }
+        }
+
+        #[test]
+        fn prop_sort_versions_desc_sorts(texts in prop::collection::vec(version_text(), 0..20), junk in prop::collection::vec("[a-z]{1,6}", 0..3)) {
+            let mut items: Vec<String> = texts.iter().chain(junk.iter()).cloned().collect();
+            sort_versions_desc(&mut items, |item| item.as_str());
+            let (parsed, rest) = items.split_at(texts.len());
+            for pair in parsed.windows(2) {
+                prop_assert!(v(&pair[0]) >= v(&pair[1]), "{} sorted before {}", pair[0], pair[1]);
+            }
+            prop_assert!(rest.iter().all(|item| item.parse::<SemVer>().is_err()));
+            let mut sorted_input = texts.clone();
+            let mut sorted_output = parsed.to_vec();
+            sorted_input.sort();
+            sorted_output.sort();
+            prop_assert_eq!(sorted_input, sorted_output);
+        }
+    }
+}
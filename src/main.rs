@@ -1,55 +1,81 @@
 mod commands;
 mod helpers;
+mod providers;
 
 use commands::{
-    download_app, list_app, php::handle_php_installation, xampp::set_xampp_php
+    cache::{cache_clean, cache_list, cache_prune, cache_size}, download_app, install::install_app, list_app, remove::remove_downloads, rollback::rollback_install,
+    uninstall::uninstall_app, upgrade::upgrade_installs, verify::verify_downloads, xampp::set_xampp_php
 };
 use fli::{Fli, init_fli_from_toml};
+use providers::providers;
 
 //  a one general cli tool to update and manage version of all the tools in the system, like php, mysql, node,js versions in a system even when using node or xampp , laragon etc
 fn main() {
     let mut app = init_fli_from_toml!();
 
     // A command to list all available versions of tools
-    let mut list_app = app.command("list", "List all available versions of tools");
-    setup_list_app(&mut list_app);
+    let list_app = app.command("list", "List all available versions of tools");
+    setup_list_app(list_app);
 
     // A command to get a specific version of a tool
-    let mut get_app = app.command("get", "Get a specific version of a tool");
-    setup_get_app(&mut get_app);
+    let get_app = app.command("get", "Get a specific version of a tool");
+    setup_get_app(get_app);
 
-    let mut install_app = app.command("install", "Install a specific version of a tool");
-    setup_install_app(&mut install_app);
+    let install_app = app.command("install", "Install a specific version of a tool");
+    setup_install_app(install_app);
 
-    let mut xampp_app = app.command("xampp", "Manage xampp modules");
-    setup_xampp_app(&mut xampp_app);
+    let xampp_app = app.command("xampp", "Manage xampp modules");
+    setup_xampp_app(xampp_app);
+
+    let verify_app = app.command("verify", "Verify downloaded archives against the release manifest");
+    setup_verify_app(verify_app);
+
+    let remove_app = app.command("remove", "Delete downloaded versions of a tool");
+    setup_remove_app(remove_app);
+
+    let uninstall_app = app.command("uninstall", "Remove an installed version from its path");
+    setup_uninstall_app(uninstall_app);
+
+    let upgrade_app = app.command("upgrade", "Reinstall installed versions whose release has a newer build");
+    setup_upgrade_app(upgrade_app);
+
+    let cache_app = app.command("cache", "Manage downloaded archives");
+    setup_cache_app(cache_app);
+
+    let rollback_app = app.command("rollback", "Restore a previous install at a path");
+    setup_rollback_app(rollback_app);
 
     app.run();
 }
 
 fn setup_list_app(app: &mut Fli) {
-    app.option(
-        "-p --php",
-        "List version of all avaiable php versions",
-        list_app,
-    );
-    // for phpmyadmin
-    app.option("-pm --phpmyadmin", "List version of all avaiable phpmyadmin versions",list_app);
+    // one flag per package, e.g -p --php
+    for provider in providers() {
+        app.option(
+            &format!("-{} --{}", provider.short_flag(), provider.name()),
+            &format!("List version of all avaiable {} versions", provider.name()),
+            list_app,
+        );
+    }
     app.option("-o --online", "Get all online", |_x| {});
+    app.option("-i --installed", "List the versions installed by xupg and where", list_app);
+    app.option("-r --refresh", "Fetch the release manifest again instead of using the cached copy", |_x| {});
+    app.option("--insecure", "Accept a release manifest that is unsigned or fails signature verification", |_x| {});
     app.allow_duplicate_callback(false);
 }
 
 fn setup_get_app(app: &mut Fli) {
-    app.option(
-        "-p --php, <...>",
-        "Get a specific version of php",
-        download_app,
-    );
-    app.option(
-        "-pm --phpmyadmin, <...>",
-        "Get a specific version of phpmyadmin",
-        download_app,
-    );
+    for provider in providers() {
+        app.option(
+            &format!("-{} --{}, <...>", provider.short_flag(), provider.name()),
+            &format!("Get a specific version of {}", provider.name()),
+            download_app,
+        );
+    }
+    app.option("-j --jobs, <>", "How many files to download at the same time", |_x| {});
+    app.option("-l --limit-rate, <>", "Cap the combined download speed, e.g 500K or 2M per second", |_x| {});
+    app.option("-r --refresh", "Fetch the release manifest again instead of using the cached copy", |_x| {});
+    app.option("--insecure", "Accept a release manifest that is unsigned or fails signature verification", |_x| {});
     app.allow_duplicate_callback(false);
 }
 
@@ -70,20 +96,94 @@ fn setup_xampp_app(app: &mut Fli) {
         "Specify the installation path of xampp",
         |_x| {},
     );
+    php_commnad.option(
+        "-sc --strip-components, <>",
+        "Strip this many leading directories from the archive (a single top-level directory is stripped by default)",
+        |_x| {},
+    );
+    php_commnad.option("--insecure", "Accept a release manifest that is unsigned or fails signature verification", |_x| {});
     php_commnad.allow_duplicate_callback(false);
     app.allow_duplicate_callback(false);
 }
 
 fn setup_install_app(app: &mut Fli) {
-    app.option(
-        "-p --php, <>",
-        "Install a specific version of php",
-        handle_php_installation,
-    );
+    for provider in providers() {
+        app.option(
+            &format!("-{} --{}, <>", provider.short_flag(), provider.name()),
+            &format!("Install a specific version of {}", provider.name()),
+            install_app,
+        );
+    }
     app.option(
         "-pa --path, <>",
         "Specify the installation path of the tool",
         |_x| {},
     );
+    app.option(
+        "-sc --strip-components, <>",
+        "Strip this many leading directories from the archive (a single top-level directory is stripped by default)",
+        |_x| {},
+    );
+    app.option("--insecure", "Accept a release manifest that is unsigned or fails signature verification", |_x| {});
     app.allow_duplicate_callback(false);
 }
+
+fn setup_verify_app(app: &mut Fli) {
+    app.default(verify_downloads);
+    app.option("-r --refresh", "Fetch the release manifest again instead of using the cached copy", verify_downloads);
+    app.option("--insecure", "Accept a release manifest that is unsigned or fails signature verification", verify_downloads);
+    app.allow_duplicate_callback(false);
+}
+
+fn setup_rollback_app(app: &mut Fli) {
+    app.option("-pa --path, <>", "The install path to list or restore snapshots of", rollback_install);
+    app.option("-t --to, <>", "Restore the snapshot with this number from the list", rollback_install);
+    app.allow_duplicate_callback(false);
+}
+
+fn setup_upgrade_app(app: &mut Fli) {
+    app.default(upgrade_installs);
+    app.option("-pa --path, <>", "Only upgrade the install at this path", upgrade_installs);
+    app.option("-r --refresh", "Fetch the release manifest again instead of using the cached copy", upgrade_installs);
+    app.option("--insecure", "Accept a release manifest that is unsigned or fails signature verification", upgrade_installs);
+    app.allow_duplicate_callback(false);
+}
+
+fn setup_remove_app(app: &mut Fli) {
+    for provider in providers() {
+        app.option(
+            &format!("-{} --{}, <...>", provider.short_flag(), provider.name()),
+            &format!("Delete downloaded {} versions", provider.name()),
+            remove_downloads,
+        );
+    }
+    app.option("-y --yes", "Don't ask for confirmation", |_x| {});
+    app.allow_duplicate_callback(false);
+}
+
+fn setup_uninstall_app(app: &mut Fli) {
+    app.option("-pa --path, <>", "The install path to remove", uninstall_app);
+    app.option("-y --yes", "Don't ask for confirmation", |_x| {});
+    app.allow_duplicate_callback(false);
+}
+
+fn setup_cache_app(app: &mut Fli) {
+    let list_command = app.command("list", "List downloaded archives with their size and age");
+    list_command.default(cache_list);
+
+    let size_command = app.command("size", "Show how much disk space downloads use per package");
+    size_command.default(cache_size);
+
+    let clean_command = app.command("clean", "Delete downloaded archives");
+    clean_command.default(cache_clean);
+    clean_command.option("-o --older-than, <>", "Only delete archives older than this many days", cache_clean);
+    clean_command.option("-d --dry-run", "Only show what would be deleted", cache_clean);
+    clean_command.option("-y --yes", "Don't ask for confirmation", cache_clean);
+    clean_command.allow_duplicate_callback(false);
+
+    let prune_command = app.command("prune", "Delete archives no install was made from");
+    prune_command.default(cache_prune);
+    prune_command.option("-d --dry-run", "Only show what would be deleted", cache_prune);
+    prune_command.option("-y --yes", "Don't ask for confirmation", cache_prune);
+    prune_command.allow_duplicate_callback(false);
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use colored::Colorize;
+use serde::Deserialize;
+
+use crate::helpers::{
+    api::{ManifestOptions, ReleaseInfo, ToolVersions},
+    file::{get_xupg_dir, list_files_in_dir},
+    version::SemVer,
+};
+
+use super::{command_output, find_file, manifest_versions, resolve_manifest_artifact, Artifact, PackageProvider};
+
+// a tool described by a TOML file in ~/.xupg/packages.d, e.g
+//
+// name = "mytool"
+// display_name = "MyTool"
+// flag = "mt"
+// versions = ["1.4.2", "1.5.0"]
+// lts = ["1.4.2"]
+// archive = "tar.gz"
+// strip_components = 1
+// binary = "bin/mytool"
+// version_command = "{binary} --version"
+//
+// [urls]
+// linux-x86_64 = "https://tools.internal/mytool/{version}/mytool-{version}-linux-amd64.tar.gz"
+// windows = "https://tools.internal/mytool/{version}/mytool-{version}-windows.zip"
+//
+// [sha256]
+// "1.5.0" = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeclaredPackage {
+    // lowercase name used as the long flag and for the download directory
+    pub name: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    // short flag without its dash, defaults to the name
+    #[serde(default)]
+    pub flag: Option<String>,
+    // versions that can be downloaded, read from the release manifest when left out
+    #[serde(default)]
+    pub versions: Option<Vec<String>>,
+    #[serde(default)]
+    pub lts: Vec<String>,
+    // download url per "<os>-<arch>", "<os>" or "any", with {version}, {os} and {arch} filled in
+    #[serde(default)]
+    pub urls: HashMap<String, String>,
+    // archive type like "zip" or "tar.gz" when the url doesn't end in one
+    #[serde(default)]
+    pub archive: Option<String>,
+    #[serde(default)]
+    pub strip_components: Option<usize>,
+    // path of the main executable inside an install
+    #[serde(default)]
+    pub binary: Option<String>,
+    // prints the version, {binary} and {dir} are replaced with the binary and install paths
+    #[serde(default)]
+    pub version_command: Option<String>,
+    // hex encoded sha256 of each version's archive
+    #[serde(default)]
+    pub sha256: HashMap<String, String>,
+}
+
+pub fn get_packages_dir() -> PathBuf {
+    get_xupg_dir().join("packages.d")
+}
+
+impl DeclaredPackage {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let package: DeclaredPackage = toml::from_str(&content).map_err(|e| e.to_string())?;
+        package.validate()?;
+        Ok(package)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let valid_name = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid_name(&self.name) {
+            return Err(format!("Invalid name {:?}, use lowercase letters, digits, - and _", self.name));
+        }
+        if !valid_name(self.short_flag()) {
+            return Err(format!("Invalid flag {:?}", self.short_flag()));
+        }
+        if self.versions.is_some() && self.urls.is_empty() {
+            return Err("`versions` needs at least one entry in [urls]".to_string());
+        }
+        if let Some(version) = self.versions.iter().flatten().find(|version| version.parse::<SemVer>().is_err()) {
+            return Err(format!("Invalid version {}", version));
+        }
+        Ok(())
+    }
+
+    // the url template for this os and architecture, the most specific one wins
+    fn url_template(&self, platform: &str) -> Option<&String> {
+        let arch = std::env::consts::ARCH;
+        [format!("{}-{}", platform, arch), platform.to_string(), "any".to_string()]
+            .iter()
+            .find_map(|key| self.urls.get(key))
+    }
+}
+
+impl PackageProvider for DeclaredPackage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+
+    fn short_flag(&self) -> &str {
+        self.flag.as_deref().unwrap_or(&self.name)
+    }
+
+    fn list_remote_versions(&self, platform: &str, options: &ManifestOptions) -> Result<ToolVersions, String> {
+        let Some(versions) = &self.versions else {
+            return manifest_versions(self.name(), platform, options);
+        };
+        let template = self
+            .url_template(platform)
+            .ok_or_else(|| format!("{} not available for this platform", self.name().to_uppercase().bold().blue()))?;
+        let versions = versions
+            .iter()
+            .map(|version| {
+                let url = template
+                    .replace("{version}", version)
+                    .replace("{os}", platform)
+                    .replace("{arch}", std::env::consts::ARCH);
+                let release = ReleaseInfo {
+                    version: None,
+                    url,
+                    release_date: String::new(),
+                    sha256: self.sha256.get(version).cloned(),
+                    size: None,
+                    lts: self.lts.contains(version),
+                };
+                (version.clone(), release)
+            })
+            .collect();
+        Ok(ToolVersions { versions })
+    }
+
+    fn resolve_artifact(&self, requirement: &str, platform: &str, options: &ManifestOptions) -> Result<Artifact, String> {
+        let mut artifact = resolve_manifest_artifact(self, requirement, platform, options)?;
+        artifact.extension = self.archive.clone();
+        Ok(artifact)
+    }
+
+    fn strip_components(&self) -> Option<usize> {
+        self.strip_components
+    }
+
+    fn locate_binary(&self, dir: &Path) -> Option<PathBuf> {
+        let binary = self.binary.as_ref()?;
+        find_file(dir, &[&format!("{}{}", binary, std::env::consts::EXE_SUFFIX), binary])
+    }
+
+    // the first word of the output that reads as a version, e.g "v1.5.0" in "mytool v1.5.0 (linux)"
+    fn detect_version(&self, dir: &Path) -> Option<String> {
+        let binary = self.locate_binary(dir)?;
+        let command = self.version_command.as_deref().unwrap_or("{binary} --version");
+        let mut words = command.split_whitespace().map(|word| {
+            word.replace("{binary}", &binary.to_string_lossy())
+                .replace("{dir}", &dir.to_string_lossy())
+        });
+        let program = words.next()?;
+        let args: Vec<String> = words.collect();
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        let output = command_output(Path::new(&program), &args)?;
+        output
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+            .find(|word| word.parse::<SemVer>().is_ok())
+            .map(|word| word.to_string())
+    }
+}
+
+// every valid definition in ~/.xupg/packages.d, the ones that can't be read are skipped with a warning
+pub fn load_declared_packages() -> Vec<DeclaredPackage> {
+    let mut files: Vec<PathBuf> = list_files_in_dir(&get_packages_dir())
+        .into_iter()
+        .filter(|file| file.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    let mut packages = Vec::new();
+    for file in files {
+        match DeclaredPackage::load(&file) {
+            Ok(package) => packages.push(package),
+            Err(e) => eprintln!("⚠️  {} {}: {}", "Skipping package definition".yellow(), file.display(), e.dimmed()),
+        }
+    }
+    packages
+}
@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use colored::Colorize;
+
+use crate::helpers::{
+    api::{load_releases, ManifestOptions, ToolVersions},
+    archive::archive_extension,
+    file::{get_download_dir, is_metadata_file, is_partial_download, list_files_in_dir, ArchiveMetadata},
+    package::Version,
+    version::{SemVer, VersionReq},
+};
+
+pub mod declared;
+pub mod mysql;
+pub mod node;
+pub mod php;
+pub mod phpmyadmin;
+
+// what has to be downloaded for a version
+pub struct Artifact {
+    pub version: String,
+    // the exact version the manifest says `version` is, when it says
+    pub release_version: Option<String>,
+    pub url: String,
+    // hex encoded sha256 and size in bytes, checked once the download completes
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+    // archive type like "tar.gz" to save it as, taken from the url when not set
+    pub extension: Option<String>,
+}
+
+// flags the commands already use for their own options
+const RESERVED_FLAGS: &[&str] = &["h", "o", "i", "r", "j", "l", "pa", "sc", "y", "d", "t", "s", "g"];
+const RESERVED_NAMES: &[&str] = &[
+    "help", "online", "installed", "refresh", "insecure", "jobs", "limit-rate", "path", "strip-components", "yes", "dry-run",
+    "older-than", "to", "set", "get",
+];
+
+// a tool xupg can download and install. every method but the names has a default that
+// reads the release manifest, so a tool published there only has to describe its install
+pub trait PackageProvider: Send + Sync {
+    // lowercase name used in the manifest, the download directory, the registry and as the long flag, e.g "php"
+    fn name(&self) -> &str;
+
+    // how the tool is shown to the user, e.g "PHP"
+    fn display_name(&self) -> &str;
+
+    // the short flag without its dash, e.g "p" for -p
+    fn short_flag(&self) -> &str;
+
+    // every version that can be downloaded for `platform`
+    fn list_remote_versions(&self, platform: &str, options: &ManifestOptions) -> Result<ToolVersions, String> {
+        manifest_versions(self.name(), platform, options)
+    }
+
+    // what a version or constraint the user typed asks for
+    fn parse_requirement(&self, requirement: &str, _platform: &str, _options: &ManifestOptions) -> Result<VersionReq, String> {
+        requirement.parse()
+    }
+
+    // the download a version or constraint like "^8.1" or "latest" picks
+    fn resolve_artifact(&self, requirement: &str, platform: &str, options: &ManifestOptions) -> Result<Artifact, String> {
+        resolve_manifest_artifact(self, requirement, platform, options)
+    }
+
+    // leading directories stripped from the archive when --strip-components isn't given
+    fn strip_components(&self) -> Option<usize> {
+        None
+    }
+
+    // where versions are installed (as `<root>/<version>`) when no --path is given
+    fn install_root(&self) -> Option<PathBuf> {
+        None
+    }
+
+    // runs on the extracted files before they are swapped into place
+    fn post_install(&self, _dir: &Path) -> Result<(), String> {
+        Ok(())
+    }
+
+    // the main executable of an install
+    fn locate_binary(&self, _dir: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    // the version an install reports about itself
+    fn detect_version(&self, _dir: &Path) -> Option<String> {
+        None
+    }
+
+    // downloaded archives, recognised by their metadata sidecar
+    fn get_local_versions(&self) -> Vec<Version> {
+        self.scan_local_versions().0
+    }
+
+    // downloaded archives and the files in the download directory that aren't one of them
+    fn scan_local_versions(&self) -> (Vec<Version>, Vec<PathBuf>) {
+        let mut versions = Vec::new();
+        let mut unrecognized = Vec::new();
+        for file in list_files_in_dir(&get_download_dir(self.name())) {
+            // skip downloads that haven't finished yet and the sidecars themselves
+            if !file.is_file() || is_partial_download(&file) || is_metadata_file(&file) {
+                continue;
+            }
+            match identify_download(self.name(), &file) {
+                Some(version) => versions.push(Version::new_local(self.display_name().to_string(), version, file)),
+                None => unrecognized.push(file),
+            }
+        }
+        (versions, unrecognized)
+    }
+}
+
+// the versions of a tool in the release manifest
+pub fn manifest_versions(name: &str, platform: &str, options: &ManifestOptions) -> Result<ToolVersions, String> {
+    let releases = load_releases(options).map_err(|e| e.to_string())?;
+    releases
+        .get_tool(platform, name)
+        .cloned()
+        .ok_or_else(|| format!("{} not available for this platform", name.to_uppercase().bold().blue()))
+}
+
+// the newest of a provider's remote versions a requirement picks
+pub fn resolve_manifest_artifact<P: PackageProvider + ?Sized>(
+    provider: &P,
+    requirement: &str,
+    platform: &str,
+    options: &ManifestOptions,
+) -> Result<Artifact, String> {
+    let parsed = provider.parse_requirement(requirement, platform, options)?;
+    let tool = provider.list_remote_versions(platform, options)?;
+    let (version, release) = tool
+        .resolve(&parsed)
+        .ok_or_else(|| format!("{} {}", "Version not available".red(), requirement))?;
+    Ok(Artifact {
+        version: version.to_string(),
+        release_version: release.version.clone(),
+        url: release.url.clone(),
+        sha256: release.sha256.clone(),
+        size: release.size,
+        extension: None,
+    })
+}
+
+// the version a download holds, from its sidecar or, for downloads made before sidecars
+// were written, from a `<package>-<version>.<extension>` name
+fn identify_download(package_name: &str, file: &Path) -> Option<SemVer> {
+    if let Some(metadata) = ArchiveMetadata::read(file) {
+        if !metadata.package.eq_ignore_ascii_case(package_name) {
+            return None;
+        }
+        return metadata.version.parse().ok();
+    }
+    let file_name = file.file_name()?.to_str()?;
+    let extension = archive_extension(file_name).or_else(|| file.extension()?.to_str())?;
+    file_name
+        .strip_prefix(&format!("{}-", package_name))?
+        .strip_suffix(&format!(".{}", extension))?
+        .parse()
+        .ok()
+}
+
+// every package xupg knows, new tools are registered here. packages declared in
+// ~/.xupg/packages.d come after the built-in ones and can't take their names or flags
+pub fn providers() -> &'static [Box<dyn PackageProvider>] {
+    static PROVIDERS: OnceLock<Vec<Box<dyn PackageProvider>>> = OnceLock::new();
+    PROVIDERS.get_or_init(|| {
+        let mut providers: Vec<Box<dyn PackageProvider>> = vec![Box::new(php::Php), Box::new(mysql::MySql), Box::new(phpmyadmin::PhpMyAdmin), Box::new(node::Node)];
+        for package in declared::load_declared_packages() {
+            let taken = RESERVED_FLAGS.contains(&package.short_flag())
+                || RESERVED_NAMES.contains(&package.name())
+                || providers.iter().any(|provider| {
+                    [provider.name(), provider.short_flag()]
+                        .iter()
+                        .any(|used| *used == package.name() || *used == package.short_flag())
+                });
+            if taken {
+                eprintln!(
+                    "⚠️  {} {}",
+                    "Skipping package definition".yellow(),
+                    format!("{}: its name or flag is already used", package.name()).dimmed()
+                );
+                continue;
+            }
+            providers.push(Box::new(package));
+        }
+        providers
+    })
+}
+
+// the package a lowercase name like the ones in the manifest and the install registry refers to
+pub fn find_provider(name: &str) -> Option<&'static dyn PackageProvider> {
+    providers()
+        .iter()
+        .find(|provider| provider.name().eq_ignore_ascii_case(name))
+        .map(|provider| provider.as_ref())
+}
+
+// the first of `candidates` (relative to `dir`) that exists
+pub fn find_file(dir: &Path, candidates: &[&str]) -> Option<PathBuf> {
+    candidates.iter().map(|candidate| dir.join(candidate)).find(|path| path.is_file())
+}
+
+// the trimmed output of running `binary` with `args`, None when it can't run or fails
+pub fn command_output(binary: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(binary).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!output.is_empty()).then_some(output)
+}
+
+// copy a sample config into place unless the install already has one
+pub fn copy_if_missing(dir: &Path, from: &str, to: &str) -> Result<(), String> {
+    let (from, to) = (dir.join(from), dir.join(to));
+    if to.exists() || !from.is_file() {
+        return Ok(());
+    }
+    fs::copy(&from, &to)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to copy {} to {}: {}", from.display(), to.display(), e))
+}
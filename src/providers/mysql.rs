@@ -0,0 +1,32 @@
+use std::path::{Path, PathBuf};
+
+use super::{command_output, find_file, PackageProvider};
+
+pub struct MySql;
+
+impl PackageProvider for MySql {
+    fn name(&self) -> &str {
+        "mysql"
+    }
+
+    fn display_name(&self) -> &str {
+        "MySQL"
+    }
+
+    fn short_flag(&self) -> &str {
+        "m"
+    }
+
+    fn locate_binary(&self, dir: &Path) -> Option<PathBuf> {
+        find_file(dir, &["bin/mysqld.exe", "bin/mysqld"])
+    }
+
+    // `mysqld --version` prints something like "mysqld  Ver 8.0.36 for Linux on x86_64 (MySQL Community Server - GPL)"
+    fn detect_version(&self, dir: &Path) -> Option<String> {
+        let output = command_output(&self.locate_binary(dir)?, &["--version"])?;
+        let mut words = output.split_whitespace().skip_while(|word| *word != "Ver").skip(1);
+        let version = words.next()?;
+        let end = version.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(version.len());
+        Some(version[..end].to_string())
+    }
+}
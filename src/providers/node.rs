@@ -0,0 +1,289 @@
+use std::path::{Path, PathBuf};
+
+use cached::proc_macro::cached;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::helpers::{
+    api::{ManifestOptions, ManifestSource, ReleaseInfo, ToolVersions},
+    config::get_config,
+    file::get_xupg_dir,
+    http::http_client,
+    version::VersionReq,
+};
+
+use super::{command_output, find_file, resolve_manifest_artifact, Artifact, PackageProvider};
+
+// Node.js, listed and downloaded straight from nodejs.org (or the `node_mirror` in the config)
+pub struct Node;
+
+// an entry of the mirror's index.json
+#[derive(Debug, Clone, Deserialize)]
+struct NodeRelease {
+    // e.g "v20.11.0"
+    version: String,
+    date: String,
+    // builds published for the release, e.g "linux-x64", "osx-arm64-tar", "win-x64-zip"
+    files: Vec<String>,
+    // false, or the codename of the LTS line like "Iron"
+    lts: Value,
+}
+
+impl NodeRelease {
+    fn number(&self) -> &str {
+        self.version.trim_start_matches('v')
+    }
+
+    fn codename(&self) -> Option<&str> {
+        self.lts.as_str()
+    }
+}
+
+fn fetch_text(client: &Client, url: &str) -> Result<String, String> {
+    ManifestSource::parse(url)
+        .and_then(|source| source.read_with(client))
+        .map_err(|e| e.to_string())
+}
+
+// cached per mirror, the client only decides how it is fetched
+#[cached(result = true, key = "String", convert = r#"{ mirror.clone() }"#)]
+fn load_index(client: &Client, mirror: String) -> Result<Vec<NodeRelease>, String> {
+    let body = fetch_text(client, &format!("{}/index.json", mirror))?;
+    serde_json::from_str(&body).map_err(|e| format!("Invalid Node.js release index {}/index.json: {}", mirror, e))
+}
+
+fn get_mirror() -> String {
+    get_config().node_mirror.trim_end_matches('/').to_string()
+}
+
+// the build to download for a platform, as its name in index.json and the end of its file name
+fn build_for(platform: &str) -> Option<(String, String)> {
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        "x86" => "x86",
+        "arm" => "armv7l",
+        "powerpc64" => "ppc64le",
+        "s390x" => "s390x",
+        _ => return None,
+    };
+    match platform {
+        "linux" => Some((format!("linux-{}", arch), format!("linux-{}.tar.gz", arch))),
+        "macos" => Some((format!("osx-{}-tar", arch), format!("darwin-{}.tar.gz", arch))),
+        "windows" => Some((format!("win-{}-zip", arch), format!("win-{}.zip", arch))),
+        _ => None,
+    }
+}
+
+// the sha256 SHASUMS256.txt lists for `file_name`, lines look like "<sha256>  <file name>"
+fn find_checksum(shasums: &str, file_name: &str) -> Option<String> {
+    shasums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let sha256 = parts.next()?;
+        (parts.next()? == file_name).then(|| sha256.to_lowercase())
+    })
+}
+
+// the releases in the mirror's index.json that have a build for `platform`
+fn remote_versions(client: &Client, mirror: &str, platform: &str) -> Result<ToolVersions, String> {
+    let (build, suffix) = build_for(platform).ok_or("Node.js is not available for this platform")?;
+    let versions = load_index(client, mirror.to_string())?
+        .into_iter()
+        .filter(|release| release.files.contains(&build))
+        .map(|release| {
+            let version = release.number().to_string();
+            let info = ReleaseInfo {
+                version: None,
+                url: format!("{}/v{}/node-v{}-{}", mirror, version, version, suffix),
+                release_date: release.date.clone(),
+                sha256: None,
+                size: None,
+                lts: release.codename().is_some(),
+            };
+            (version, info)
+        })
+        .collect();
+    Ok(ToolVersions { versions })
+}
+
+// "lts/*" is the newest LTS release, "lts/iron" the newest of the LTS line with that codename
+fn parse_node_requirement(client: &Client, mirror: &str, requirement: &str) -> Result<VersionReq, String> {
+    let requirement = requirement.trim().to_lowercase();
+    let Some(codename) = requirement.strip_prefix("lts/") else {
+        return requirement.parse();
+    };
+    if codename == "*" {
+        return Ok(VersionReq::Lts);
+    }
+    // every release of an LTS line shares its major version
+    let major = load_index(client, mirror.to_string())?
+        .iter()
+        .find(|release| release.codename().is_some_and(|name| name.eq_ignore_ascii_case(codename)))
+        .and_then(|release| release.number().split('.').next().map(|major| major.to_string()))
+        .ok_or_else(|| format!("Unknown Node.js LTS codename {}", codename))?;
+    format!("^{}", major).parse()
+}
+
+// the checksum of a build, from the SHASUMS256.txt published next to it
+fn fetch_checksum(client: &Client, mirror: &str, version: &str, file_name: &str) -> Result<String, String> {
+    let shasums_url = format!("{}/v{}/SHASUMS256.txt", mirror, version);
+    let shasums = fetch_text(client, &shasums_url)?;
+    find_checksum(&shasums, file_name).ok_or_else(|| format!("{} has no checksum for {}", shasums_url, file_name))
+}
+
+impl PackageProvider for Node {
+    fn name(&self) -> &str {
+        "node"
+    }
+
+    fn display_name(&self) -> &str {
+        "Node.js"
+    }
+
+    fn short_flag(&self) -> &str {
+        "n"
+    }
+
+    fn list_remote_versions(&self, platform: &str, _options: &ManifestOptions) -> Result<ToolVersions, String> {
+        remote_versions(&http_client()?, &get_mirror(), platform)
+    }
+
+    fn parse_requirement(&self, requirement: &str, _platform: &str, _options: &ManifestOptions) -> Result<VersionReq, String> {
+        parse_node_requirement(&http_client()?, &get_mirror(), requirement)
+    }
+
+    fn resolve_artifact(&self, requirement: &str, platform: &str, options: &ManifestOptions) -> Result<Artifact, String> {
+        let mut artifact = resolve_manifest_artifact(self, requirement, platform, options)?;
+        let file_name = artifact.url.rsplit('/').next().unwrap_or_default().to_string();
+        artifact.sha256 = Some(fetch_checksum(&http_client()?, &get_mirror(), &artifact.version, &file_name)?);
+        Ok(artifact)
+    }
+
+    fn install_root(&self) -> Option<PathBuf> {
+        Some(get_xupg_dir().join("installs").join("node"))
+    }
+
+    // node.exe sits at the root of windows builds, other builds keep it in bin
+    fn locate_binary(&self, dir: &Path) -> Option<PathBuf> {
+        find_file(dir, &["node.exe", "bin/node"])
+    }
+
+    fn detect_version(&self, dir: &Path) -> Option<String> {
+        let version = command_output(&self.locate_binary(dir)?, &["--version"])?;
+        Some(version.trim_start_matches('v').to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::helpers::{config::Config, http::build_client};
+
+    // a client built from the defaults, so the user's config (proxy, timeouts, CA certs) plays no part
+    fn client() -> Client {
+        build_client(&Config::default()).unwrap()
+    }
+
+    // a mirror on localhost serving `files` by path, unknown paths get a 404
+    fn serve(files: Vec<(String, String)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                    line.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let response = match files.iter().find(|(file, _)| file == path) {
+                    Some((_, body)) => format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body),
+                    None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+                };
+                let _ = (&stream).write_all(response.as_bytes());
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    fn linux_build() -> String {
+        build_for("linux").unwrap().0
+    }
+
+    fn index() -> String {
+        let build = linux_build();
+        format!(
+            r#"[
+                {{"version": "v22.1.0", "date": "2024-05-02", "files": ["{build}"], "lts": false}},
+                {{"version": "v20.12.2", "date": "2024-04-10", "files": ["{build}"], "lts": "Iron"}},
+                {{"version": "v20.11.0", "date": "2024-01-09", "files": ["{build}"], "lts": "Iron"}},
+                {{"version": "v19.9.0", "date": "2023-04-10", "files": ["win-x64-zip"], "lts": false}},
+                {{"version": "v18.20.2", "date": "2024-04-10", "files": ["{build}"], "lts": "Hydrogen"}}
+            ]"#
+        )
+    }
+
+    fn resolve(mirror: &str, requirement: &str) -> Option<String> {
+        let requirement = parse_node_requirement(&client(), mirror, requirement).unwrap();
+        let versions = remote_versions(&client(), mirror, "linux").unwrap();
+        versions.resolve(&requirement).map(|(version, _)| version.to_string())
+    }
+
+    #[test]
+    fn finds_checksums_by_file_name() {
+        let shasums = "ABC123  node-v20.11.0-linux-x64.tar.gz\n\
+                       def456  node-v20.11.0-linux-x64.tar.xz\n\
+                       \n\
+                       789fed  node-v20.11.0-win-x64.zip\n";
+        assert_eq!(find_checksum(shasums, "node-v20.11.0-linux-x64.tar.gz").as_deref(), Some("abc123"));
+        assert_eq!(find_checksum(shasums, "node-v20.11.0-win-x64.zip").as_deref(), Some("789fed"));
+        assert_eq!(find_checksum(shasums, "node-v20.11.0-linux-x64"), None);
+        assert_eq!(find_checksum(shasums, "node-v20.11.0-darwin-x64.tar.gz"), None);
+    }
+
+    #[test]
+    fn lists_the_releases_built_for_the_platform() {
+        let mirror = serve(vec![("/index.json".to_string(), index())]);
+        let versions = remote_versions(&client(), &mirror, "linux").unwrap();
+        let mut listed: Vec<&String> = versions.versions.keys().collect();
+        listed.sort();
+        assert_eq!(listed, ["18.20.2", "20.11.0", "20.12.2", "22.1.0"]);
+
+        let release = &versions.versions["20.12.2"];
+        let suffix = build_for("linux").unwrap().1;
+        assert_eq!(release.url, format!("{}/v20.12.2/node-v20.12.2-{}", mirror, suffix));
+        assert_eq!(release.release_date, "2024-04-10");
+        assert!(release.lts);
+        assert!(!versions.versions["22.1.0"].lts);
+    }
+
+    #[test]
+    fn resolves_lts_codenames() {
+        let mirror = serve(vec![("/index.json".to_string(), index())]);
+        assert_eq!(resolve(&mirror, "lts/iron").as_deref(), Some("20.12.2"));
+        assert_eq!(resolve(&mirror, "lts/Hydrogen").as_deref(), Some("18.20.2"));
+        assert_eq!(resolve(&mirror, "lts/*").as_deref(), Some("20.12.2"));
+        assert_eq!(resolve(&mirror, "latest").as_deref(), Some("22.1.0"));
+        assert_eq!(resolve(&mirror, "^20.11").as_deref(), Some("20.12.2"));
+        assert!(parse_node_requirement(&client(), &mirror, "lts/argon").is_err());
+    }
+
+    #[test]
+    fn fetches_checksums_from_the_mirror() {
+        let shasums = "0123abcd  node-v20.12.2-linux-x64.tar.gz\n".to_string();
+        let mirror = serve(vec![("/v20.12.2/SHASUMS256.txt".to_string(), shasums)]);
+        assert_eq!(fetch_checksum(&client(), &mirror, "20.12.2", "node-v20.12.2-linux-x64.tar.gz").unwrap(), "0123abcd");
+        assert!(fetch_checksum(&client(), &mirror, "20.12.2", "node-v20.12.2-win-x64.zip").is_err());
+        assert!(fetch_checksum(&client(), &mirror, "20.11.0", "node-v20.11.0-linux-x64.tar.gz").is_err());
+    }
+}
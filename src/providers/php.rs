@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+
+use super::{command_output, copy_if_missing, find_file, PackageProvider};
+
+pub struct Php;
+
+impl PackageProvider for Php {
+    fn name(&self) -> &str {
+        "php"
+    }
+
+    fn display_name(&self) -> &str {
+        "PHP"
+    }
+
+    fn short_flag(&self) -> &str {
+        "p"
+    }
+
+    // windows builds only ship php.ini templates
+    fn post_install(&self, dir: &Path) -> Result<(), String> {
+        copy_if_missing(dir, "php.ini-development", "php.ini")
+    }
+
+    // php.exe sits at the root of windows builds, other builds keep it in bin
+    fn locate_binary(&self, dir: &Path) -> Option<PathBuf> {
+        find_file(dir, &["php.exe", "bin/php", "php"])
+    }
+
+    fn detect_version(&self, dir: &Path) -> Option<String> {
+        command_output(&self.locate_binary(dir)?, &["-r", "echo PHP_VERSION;"])
+    }
+}
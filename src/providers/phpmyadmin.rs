@@ -0,0 +1,39 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{copy_if_missing, find_file, PackageProvider};
+
+pub struct PhpMyAdmin;
+
+impl PackageProvider for PhpMyAdmin {
+    fn name(&self) -> &str {
+        "phpmyadmin"
+    }
+
+    fn display_name(&self) -> &str {
+        "PHPMyAdmin"
+    }
+
+    fn short_flag(&self) -> &str {
+        "pm"
+    }
+
+    fn post_install(&self, dir: &Path) -> Result<(), String> {
+        copy_if_missing(dir, "config.sample.inc.php", "config.inc.php")
+    }
+
+    // a web app, the closest thing to a binary is the page it is served from
+    fn locate_binary(&self, dir: &Path) -> Option<PathBuf> {
+        find_file(dir, &["index.php"])
+    }
+
+    // releases carry a `RELEASE-DATE-<version>` file at their root
+    fn detect_version(&self, dir: &Path) -> Option<String> {
+        fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix("RELEASE-DATE-").map(|version| version.to_string())
+        })
+    }
+}